@@ -0,0 +1,28 @@
+use std::fmt::Write;
+use std::path::Path;
+
+#[allow(dead_code)]
+#[path = "tools/mkdisk.rs"]
+mod mkdisk;
+
+// Embeds every file in programs/ into the kernel, which falls back on them when
+// there is no disk. disk.img is left to tools/mkdisk.rs, as the kernel writes
+// to it while it runs.
+fn main() {
+    let root = Path::new(&std::env::var("CARGO_MANIFEST_DIR").unwrap()).to_path_buf();
+    let programs = root.join("programs");
+
+    let mut embedded = String::from("&[\n");
+    for (name, _) in mkdisk::read_programs(&programs).expect("could not read programs/") {
+        let path = programs.join(&name);
+        writeln!(embedded, "    ({:?}, include_str!({:?})),", name, path.to_str().unwrap()).unwrap();
+    }
+    embedded.push(']');
+    let out = Path::new(&std::env::var("OUT_DIR").unwrap()).join("programs.rs");
+    std::fs::write(out, embedded).expect("could not write programs.rs");
+
+    println!("cargo:rerun-if-changed=programs");
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-changed=tools/mkdisk.rs");
+    println!("cargo:rerun-if-changed=src/image.rs");
+}
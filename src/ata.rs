@@ -0,0 +1,201 @@
+use x86_64::instructions::port::Port;
+
+use crate::image::BlockDevice;
+
+// Polled 28-bit LBA PIO driver for the slave drive on the primary ATA bus,
+// which is where QEMU puts `-drive ...,index=1`. The boot disk is the master.
+
+const DATA: u16 = 0x1F0;
+const SECTOR_COUNT: u16 = 0x1F2;
+const LBA_LOW: u16 = 0x1F3;
+const LBA_MID: u16 = 0x1F4;
+const LBA_HIGH: u16 = 0x1F5;
+const DRIVE: u16 = 0x1F6;
+const COMMAND: u16 = 0x1F7;
+const STATUS: u16 = 0x1F7;
+const CONTROL: u16 = 0x3F6;
+
+const STATUS_ERR: u8 = 0x01;
+const STATUS_DRQ: u8 = 0x08;
+const STATUS_DF: u8 = 0x20;
+const STATUS_BSY: u8 = 0x80;
+
+const CMD_READ_SECTORS: u8 = 0x20;
+const CMD_WRITE_SECTORS: u8 = 0x30;
+const CMD_FLUSH_CACHE: u8 = 0xE7;
+const CMD_IDENTIFY: u8 = 0xEC;
+
+// Stops the drive raising IRQ 14, which nothing handles as the driver polls.
+const CONTROL_NIEN: u8 = 0x02;
+
+const SELECT_SLAVE: u8 = 0xB0;
+const SELECT_SLAVE_LBA: u8 = 0xF0;
+
+const SECTOR_SIZE: usize = 512;
+const POLL_LIMIT: usize = 1_000_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AtaError {
+    DeviceError,
+    Timeout,
+    OutOfRange,
+}
+
+pub struct AtaDisk {
+    sectors: usize,
+}
+
+fn inb(port: u16) -> u8 {
+    unsafe { Port::<u8>::new(port).read() }
+}
+
+fn outb(port: u16, value: u8) {
+    unsafe { Port::<u8>::new(port).write(value) }
+}
+
+fn inw(port: u16) -> u16 {
+    unsafe { Port::<u16>::new(port).read() }
+}
+
+fn outw(port: u16, value: u16) {
+    unsafe { Port::<u16>::new(port).write(value) }
+}
+
+impl AtaDisk {
+    // Asks the drive to identify itself, returning None when there is no ATA
+    // drive attached.
+    pub fn open() -> Option<Self> {
+        outb(DRIVE, SELECT_SLAVE);
+        Self::settle();
+        outb(CONTROL, CONTROL_NIEN);
+        outb(SECTOR_COUNT, 0);
+        outb(LBA_LOW, 0);
+        outb(LBA_MID, 0);
+        outb(LBA_HIGH, 0);
+        outb(COMMAND, CMD_IDENTIFY);
+        let status = inb(STATUS);
+        if status == 0 || status == 0xFF {
+            return None;
+        }
+        Self::wait_not_busy().ok()?;
+        if inb(LBA_MID) != 0 || inb(LBA_HIGH) != 0 {
+            return None;
+        }
+        Self::wait_data().ok()?;
+        let mut identify = [0u16; SECTOR_SIZE / 2];
+        for word in identify.iter_mut() {
+            *word = inw(DATA);
+        }
+        let sectors = identify[60] as usize | (identify[61] as usize) << 16;
+        Some(Self {sectors})
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.sectors * SECTOR_SIZE
+    }
+
+    // Reading the status port takes long enough that four reads give the
+    // drive the 400ns it needs after being selected.
+    fn settle() {
+        for _ in 0..4 {
+            inb(STATUS);
+        }
+    }
+
+    fn wait_not_busy() -> Result<u8, AtaError> {
+        for _ in 0..POLL_LIMIT {
+            let status = inb(STATUS);
+            if status & STATUS_BSY == 0 {
+                return Ok(status);
+            }
+        }
+        Err(AtaError::Timeout)
+    }
+
+    fn wait_data() -> Result<(), AtaError> {
+        for _ in 0..POLL_LIMIT {
+            let status = inb(STATUS);
+            if status & (STATUS_ERR | STATUS_DF) != 0 {
+                return Err(AtaError::DeviceError);
+            }
+            if status & STATUS_BSY == 0 && status & STATUS_DRQ != 0 {
+                return Ok(());
+            }
+        }
+        Err(AtaError::Timeout)
+    }
+
+    fn start(&self, lba: usize, command: u8) -> Result<(), AtaError> {
+        if lba >= self.sectors {
+            return Err(AtaError::OutOfRange);
+        }
+        Self::wait_not_busy()?;
+        outb(DRIVE, SELECT_SLAVE_LBA | ((lba >> 24) & 0x0F) as u8);
+        Self::settle();
+        outb(SECTOR_COUNT, 1);
+        outb(LBA_LOW, lba as u8);
+        outb(LBA_MID, (lba >> 8) as u8);
+        outb(LBA_HIGH, (lba >> 16) as u8);
+        outb(COMMAND, command);
+        Self::wait_data()
+    }
+
+    pub fn read_sector(&mut self, lba: usize, buffer: &mut [u8; SECTOR_SIZE]) -> Result<(), AtaError> {
+        self.start(lba, CMD_READ_SECTORS)?;
+        for pair in buffer.chunks_mut(2) {
+            pair.copy_from_slice(&inw(DATA).to_le_bytes());
+        }
+        Ok(())
+    }
+
+    pub fn write_sector(&mut self, lba: usize, buffer: &[u8; SECTOR_SIZE]) -> Result<(), AtaError> {
+        self.start(lba, CMD_WRITE_SECTORS)?;
+        for pair in buffer.chunks(2) {
+            outw(DATA, u16::from_le_bytes([pair[0], pair[1]]));
+        }
+        outb(COMMAND, CMD_FLUSH_CACHE);
+        let status = Self::wait_not_busy()?;
+        if status & (STATUS_ERR | STATUS_DF) != 0 {
+            return Err(AtaError::DeviceError);
+        }
+        Ok(())
+    }
+}
+
+// Blocks need not line up with sectors, so each block is copied in pieces
+// from (or read-modify-written into) the sectors it overlaps.
+impl<const BLOCK_SIZE: usize> BlockDevice<BLOCK_SIZE> for AtaDisk {
+    type Error = AtaError;
+
+    fn read_block(&mut self, block: usize, buffer: &mut [u8; BLOCK_SIZE]) -> Result<(), AtaError> {
+        let start = block * BLOCK_SIZE;
+        let mut done = 0;
+        let mut sector = [0; SECTOR_SIZE];
+        while done < BLOCK_SIZE {
+            let offset = (start + done) % SECTOR_SIZE;
+            let len = (SECTOR_SIZE - offset).min(BLOCK_SIZE - done);
+            self.read_sector((start + done) / SECTOR_SIZE, &mut sector)?;
+            buffer[done..done + len].copy_from_slice(&sector[offset..offset + len]);
+            done += len;
+        }
+        Ok(())
+    }
+
+    fn write_block(&mut self, block: usize, buffer: &[u8; BLOCK_SIZE]) -> Result<(), AtaError> {
+        let start = block * BLOCK_SIZE;
+        let mut done = 0;
+        let mut sector = [0; SECTOR_SIZE];
+        while done < BLOCK_SIZE {
+            let lba = (start + done) / SECTOR_SIZE;
+            let offset = (start + done) % SECTOR_SIZE;
+            let len = (SECTOR_SIZE - offset).min(BLOCK_SIZE - done);
+            if len < SECTOR_SIZE {
+                self.read_sector(lba, &mut sector)?;
+            }
+            sector[offset..offset + len].copy_from_slice(&buffer[done..done + len]);
+            self.write_sector(lba, &sector)?;
+            done += len;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,120 @@
+use core::ops::Range;
+
+// The VGA text buffer: 80x25 cells at 0xb8000, each a code page 437
+// character and a color byte.
+pub const BUFFER_WIDTH: usize = 80;
+pub const BUFFER_HEIGHT: usize = 25;
+
+#[cfg(target_os = "none")]
+const VGA_BUFFER: usize = 0xb8000;
+
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Color {
+    Black = 0,
+    Blue = 1,
+    Green = 2,
+    Cyan = 3,
+    Red = 4,
+    Magenta = 5,
+    Brown = 6,
+    LightGray = 7,
+    DarkGray = 8,
+    LightBlue = 9,
+    LightGreen = 10,
+    LightCyan = 11,
+    LightRed = 12,
+    Pink = 13,
+    Yellow = 14,
+    White = 15,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ColorCode(u8);
+
+impl ColorCode {
+    pub const fn new(foreground: Color, background: Color) -> ColorCode {
+        ColorCode((background as u8) << 4 | (foreground as u8))
+    }
+}
+
+// Characters that look the same in code page 437 as in ASCII.
+pub fn is_drawable(c: char) -> bool {
+    matches!(c, ' '..='~' | '\n')
+}
+
+// Where the kernel puts the characters of its screen.
+pub trait Display {
+    fn plot(&mut self, c: char, col: usize, row: usize, color: ColorCode);
+}
+
+// The real screen. Characters outside ASCII come out as a filled box.
+#[cfg(target_os = "none")]
+pub struct Vga;
+
+#[cfg(target_os = "none")]
+impl Display for Vga {
+    fn plot(&mut self, c: char, col: usize, row: usize, color: ColorCode) {
+        if col >= BUFFER_WIDTH || row >= BUFFER_HEIGHT {
+            return;
+        }
+        let byte = if c.is_ascii() {c as u8} else {0xfe};
+        let cell = (VGA_BUFFER as *mut u16).wrapping_add(row * BUFFER_WIDTH + col);
+        unsafe { cell.write_volatile((color.0 as u16) << 8 | byte as u16) };
+    }
+}
+
+// A screen kept in memory so that tests can look at what was drawn.
+pub struct MemoryDisplay {
+    chars: [[char; BUFFER_WIDTH]; BUFFER_HEIGHT],
+    colors: [[ColorCode; BUFFER_WIDTH]; BUFFER_HEIGHT],
+}
+
+impl Default for MemoryDisplay {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MemoryDisplay {
+    pub fn new() -> Self {
+        Self {
+            chars: [[' '; BUFFER_WIDTH]; BUFFER_HEIGHT],
+            colors: [[ColorCode::new(Color::White, Color::Black); BUFFER_WIDTH]; BUFFER_HEIGHT],
+        }
+    }
+
+    pub fn char_at(&self, col: usize, row: usize) -> char {
+        self.chars[row][col]
+    }
+
+    pub fn color_at(&self, col: usize, row: usize) -> ColorCode {
+        self.colors[row][col]
+    }
+
+    pub fn row(&self, row: usize) -> &[char; BUFFER_WIDTH] {
+        &self.chars[row]
+    }
+
+    // Where `text` appears on one row of the area covered by `cols` and
+    // `rows`, as a row and column relative to the area.
+    pub fn find(&self, cols: Range<usize>, rows: Range<usize>, text: &str) -> Option<(usize, usize)> {
+        let len = text.chars().count();
+        for row in rows.clone() {
+            for col in cols.start..(cols.end + 1).saturating_sub(len) {
+                if text.chars().zip(&self.chars[row][col..]).all(|(t, c)| t == *c) {
+                    return Some((row - rows.start, col - cols.start));
+                }
+            }
+        }
+        None
+    }
+}
+
+impl Display for MemoryDisplay {
+    fn plot(&mut self, c: char, col: usize, row: usize, color: ColorCode) {
+        self.chars[row][col] = c;
+        self.colors[row][col] = color;
+    }
+}
@@ -0,0 +1,241 @@
+// Text being edited in a window, held as the raw bytes of the file with a
+// cursor index into them. Rows and columns are derived from the newlines.
+// `top` and `left` are the first row and column visible in the window.
+pub struct Editor<const CAPACITY: usize> {
+    text: [u8; CAPACITY],
+    len: usize,
+    cursor: usize,
+    top: usize,
+    left: usize,
+}
+
+impl<const CAPACITY: usize> Editor<CAPACITY> {
+    pub fn new() -> Self {
+        Self {text: [0; CAPACITY], len: 0, cursor: 0, top: 0, left: 0}
+    }
+
+    pub fn load(&mut self, bytes: &[u8]) {
+        self.len = bytes.len().min(CAPACITY);
+        self.text[0..self.len].copy_from_slice(&bytes[0..self.len]);
+        self.cursor = 0;
+        self.top = 0;
+        self.left = 0;
+    }
+
+    pub fn text(&self) -> &[u8] {
+        &self.text[0..self.len]
+    }
+
+    pub fn lines(&self) -> impl Iterator<Item = &[u8]> {
+        self.text().split(|c| *c == b'\n')
+    }
+
+    // Writes the text out as newline-separated lines with trailing blanks
+    // removed, returning the number of bytes written. Text without trailing
+    // blanks comes out exactly as it was loaded.
+    pub fn save_into(&self, buffer: &mut [u8]) -> usize {
+        let mut count = 0;
+        for (i, line) in self.lines().enumerate() {
+            if i > 0 {
+                buffer[count] = b'\n';
+                count += 1;
+            }
+            let len = line.iter().rposition(|c| *c != b' ' && *c != b'\t').map_or(0, |last| last + 1);
+            buffer[count..count + len].copy_from_slice(&line[0..len]);
+            count += len;
+        }
+        count
+    }
+
+    // Row and column of the cursor.
+    pub fn cursor_position(&self) -> (usize, usize) {
+        let row = self.text[0..self.cursor].iter().filter(|c| **c == b'\n').count();
+        (row, self.cursor - self.line_start(self.cursor))
+    }
+
+    // First visible row and column.
+    pub fn viewport(&self) -> (usize, usize) {
+        (self.top, self.left)
+    }
+
+    // Moves the viewport the least amount needed to keep the cursor on screen.
+    pub fn scroll_to_cursor(&mut self, height: usize, width: usize) {
+        let (row, col) = self.cursor_position();
+        if row < self.top {
+            self.top = row;
+        } else if row >= self.top + height {
+            self.top = row + 1 - height;
+        }
+        if col < self.left {
+            self.left = col;
+        } else if col >= self.left + width {
+            self.left = col + 1 - width;
+        }
+    }
+
+    pub fn insert(&mut self, c: u8) {
+        if self.len == CAPACITY {
+            return;
+        }
+        self.text.copy_within(self.cursor..self.len, self.cursor + 1);
+        self.text[self.cursor] = c;
+        self.len += 1;
+        self.cursor += 1;
+    }
+
+    pub fn backspace(&mut self) {
+        if self.cursor > 0 {
+            self.cursor -= 1;
+            self.delete();
+        }
+    }
+
+    pub fn delete(&mut self) {
+        if self.cursor < self.len {
+            self.text.copy_within(self.cursor + 1..self.len, self.cursor);
+            self.len -= 1;
+        }
+    }
+
+    pub fn left(&mut self) {
+        if self.cursor > 0 {
+            self.cursor -= 1;
+        }
+    }
+
+    pub fn right(&mut self) {
+        if self.cursor < self.len {
+            self.cursor += 1;
+        }
+    }
+
+    pub fn up(&mut self) {
+        let start = self.line_start(self.cursor);
+        if start > 0 {
+            let col = self.cursor - start;
+            let prev_start = self.line_start(start - 1);
+            self.cursor = (prev_start + col).min(start - 1);
+        }
+    }
+
+    pub fn down(&mut self) {
+        let end = self.line_end(self.cursor);
+        if end < self.len {
+            let col = self.cursor - self.line_start(self.cursor);
+            self.cursor = (end + 1 + col).min(self.line_end(end + 1));
+        }
+    }
+
+    pub fn home(&mut self) {
+        self.cursor = self.line_start(self.cursor);
+    }
+
+    pub fn end(&mut self) {
+        self.cursor = self.line_end(self.cursor);
+    }
+
+    // Index of the first character on the line containing `pos`.
+    fn line_start(&self, pos: usize) -> usize {
+        match self.text[0..pos].iter().rposition(|c| *c == b'\n') {
+            Some(newline) => newline + 1,
+            None => 0,
+        }
+    }
+
+    // Index of the newline (or end of text) closing the line containing `pos`.
+    fn line_end(&self, pos: usize) -> usize {
+        match self.text[pos..self.len].iter().position(|c| *c == b'\n') {
+            Some(newline) => pos + newline,
+            None => self.len,
+        }
+    }
+}
+
+#[cfg(all(test, not(target_os = "none")))]
+mod tests {
+    use super::*;
+
+    fn loaded(text: &str) -> Editor<64> {
+        let mut editor = Editor::new();
+        editor.load(text.as_bytes());
+        editor
+    }
+
+    fn saved<const C: usize>(editor: &Editor<C>) -> Vec<u8> {
+        let mut buffer = [0; C];
+        let count = editor.save_into(&mut buffer);
+        buffer[0..count].to_vec()
+    }
+
+    #[test]
+    fn text_saves_exactly_as_loaded() {
+        let text = "print(1)\n\n  x := 2\nprint(x)\n";
+        assert_eq!(saved(&loaded(text)), text.as_bytes());
+    }
+
+    #[test]
+    fn saving_trims_trailing_blanks_from_each_line() {
+        let editor = loaded("x := 1 \t\n  \nprint(x)\t");
+        assert_eq!(saved(&editor), b"x := 1\n\nprint(x)");
+    }
+
+    #[test]
+    fn cursor_stops_at_the_end_of_shorter_lines() {
+        let mut editor = loaded("abcd\nx\nefgh");
+        editor.end();
+        assert_eq!(editor.cursor_position(), (0, 4));
+        editor.down();
+        assert_eq!(editor.cursor_position(), (1, 1));
+        editor.down();
+        assert_eq!(editor.cursor_position(), (2, 1));
+        editor.end();
+        editor.up();
+        editor.up();
+        assert_eq!(editor.cursor_position(), (0, 1));
+        editor.down();
+        editor.right();
+        assert_eq!(editor.cursor_position(), (2, 0));
+        editor.up();
+        editor.up();
+        editor.home();
+        editor.left();
+        assert_eq!(editor.cursor_position(), (0, 0));
+        editor.up();
+        assert_eq!(editor.cursor_position(), (0, 0));
+    }
+
+    #[test]
+    fn editing_happens_at_the_cursor() {
+        let mut editor = loaded("ac\nd");
+        editor.right();
+        editor.insert(b'b');
+        editor.end();
+        editor.delete();
+        assert_eq!(editor.text(), b"abcd");
+        editor.insert(b'\n');
+        assert_eq!(editor.cursor_position(), (1, 0));
+        editor.backspace();
+        assert_eq!(editor.text(), b"abcd");
+        assert_eq!(editor.cursor_position(), (0, 3));
+    }
+
+    #[test]
+    fn viewport_follows_the_cursor() {
+        let mut editor = loaded("0\n1\n2\n3\n4\n5\nlong line");
+        for _ in 0..6 {
+            editor.down();
+        }
+        editor.end();
+        editor.scroll_to_cursor(3, 4);
+        assert_eq!(editor.viewport(), (4, 6));
+        editor.home();
+        editor.up();
+        editor.scroll_to_cursor(3, 4);
+        assert_eq!(editor.viewport(), (4, 0));
+        for _ in 0..5 {
+            editor.up();
+        }
+        editor.scroll_to_cursor(3, 4);
+        assert_eq!(editor.viewport(), (0, 0));
+    }
+}
@@ -0,0 +1,105 @@
+use core::fmt::{self, Debug, Write};
+use core::panic::Location;
+use core::str::from_utf8;
+
+const MESSAGE_BYTES: usize = 76;
+const FILE_BYTES: usize = 32;
+
+// Something the kernel can't carry on from, such as a file it has just listed
+// failing to open, along with where it happened. The kernel returns a Fault
+// where it would otherwise unwrap, and the panic handler makes one from any
+// panic that gets past that, so both end up on the fault screen. The location
+// is copied, as a panic's only lives as long as the panic.
+#[derive(Debug)]
+pub struct Fault {
+    message: [u8; MESSAGE_BYTES],
+    len: u8,
+    file: [u8; FILE_BYTES],
+    file_len: u8,
+    line: u32,
+    column: u32,
+}
+
+impl Fault {
+    #[track_caller]
+    pub fn new(args: fmt::Arguments) -> Self {
+        Self::at(Location::caller(), args)
+    }
+
+    pub fn at(location: &Location, args: fmt::Arguments) -> Self {
+        let mut fault = Self {
+            message: [0; MESSAGE_BYTES],
+            len: 0,
+            file: [0; FILE_BYTES],
+            file_len: 0,
+            line: location.line(),
+            column: location.column(),
+        };
+        // Keeps the end of a long path, which says the most.
+        let file = location.file();
+        let mut start = file.len().saturating_sub(FILE_BYTES);
+        while !file.is_char_boundary(start) {
+            start += 1;
+        }
+        fault.file[0..file.len() - start].copy_from_slice(&file.as_bytes()[start..]);
+        fault.file_len = (file.len() - start) as u8;
+        let _ = fault.write_fmt(args);
+        fault
+    }
+
+    pub fn message(&self) -> &str {
+        from_utf8(&self.message[0..self.len as usize]).unwrap_or("")
+    }
+
+    pub fn file(&self) -> &str {
+        from_utf8(&self.file[0..self.file_len as usize]).unwrap_or("")
+    }
+
+    pub fn line(&self) -> u32 {
+        self.line
+    }
+
+    pub fn column(&self) -> u32 {
+        self.column
+    }
+}
+
+// Keeps as much of the message as fits, dropping anything that isn't ASCII.
+impl Write for Fault {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for c in s.chars().filter(char::is_ascii) {
+            if (self.len as usize) < MESSAGE_BYTES {
+                self.message[self.len as usize] = c as u8;
+                self.len += 1;
+            }
+        }
+        Ok(())
+    }
+}
+
+// Turns a failed Result or an empty Option into a Fault recorded at the
+// caller, described by `what`.
+pub trait OrFault<T> {
+    #[track_caller]
+    fn or_fault(self, what: &str) -> Result<T, Fault>;
+}
+
+impl<T, E: Debug> OrFault<T> for Result<T, E> {
+    #[track_caller]
+    fn or_fault(self, what: &str) -> Result<T, Fault> {
+        match self {
+            Ok(value) => Ok(value),
+            Err(e) => Err(Fault::new(format_args!("{}: {:?}", what, e))),
+        }
+    }
+}
+
+impl<T> OrFault<T> for Option<T> {
+    #[track_caller]
+    fn or_fault(self, what: &str) -> Result<T, Fault> {
+        match self {
+            Some(value) => Ok(value),
+            None => Err(Fault::new(format_args!("{}", what))),
+        }
+    }
+}
@@ -0,0 +1,157 @@
+// Layout of the disk that keeps files between boots. Block 0 holds MAGIC
+// followed by the number of files. File `i` lives in a slot of
+// 1 + MAX_FILE_BLOCKS blocks starting at block 1 + i * (1 + MAX_FILE_BLOCKS):
+// one block with its name and length (u16, little endian), then its contents.
+//
+// This file has no dependencies on the rest of the kernel so that host tools
+// can include it to build disk images.
+
+pub const MAGIC: [u8; 8] = *b"SWIMFS01";
+
+// Anything that stores fixed-size blocks, like the ramdisk the file system
+// runs on.
+pub trait BlockDevice<const BLOCK_SIZE: usize> {
+    type Error;
+    fn read_block(&mut self, block: usize, buffer: &mut [u8; BLOCK_SIZE]) -> Result<(), Self::Error>;
+    fn write_block(&mut self, block: usize, buffer: &[u8; BLOCK_SIZE]) -> Result<(), Self::Error>;
+}
+
+pub struct ImageLayout<const BLOCK_SIZE: usize, const MAX_FILE_BLOCKS: usize, const NAME_BYTES: usize>;
+
+impl<const BLOCK_SIZE: usize, const MAX_FILE_BLOCKS: usize, const NAME_BYTES: usize> ImageLayout<BLOCK_SIZE, MAX_FILE_BLOCKS, NAME_BYTES> {
+    // Bytes needed to hold `max_files` files.
+    pub fn image_bytes(max_files: usize) -> usize {
+        Self::slot(max_files) * BLOCK_SIZE
+    }
+
+    fn slot(index: usize) -> usize {
+        1 + index * (1 + MAX_FILE_BLOCKS)
+    }
+
+    // Number of files on the disk, or None if it has never been formatted.
+    pub fn read_count<D: BlockDevice<BLOCK_SIZE>>(disk: &mut D) -> Result<Option<usize>, D::Error> {
+        let mut block = [0; BLOCK_SIZE];
+        disk.read_block(0, &mut block)?;
+        if block[0..MAGIC.len()] == MAGIC {
+            Ok(Some(block[MAGIC.len()] as usize))
+        } else {
+            Ok(None)
+        }
+    }
+
+    // Marks the disk as formatted and holding `count` files.
+    pub fn write_count<D: BlockDevice<BLOCK_SIZE>>(disk: &mut D, count: usize) -> Result<(), D::Error> {
+        let mut block = [0; BLOCK_SIZE];
+        block[0..MAGIC.len()].copy_from_slice(&MAGIC);
+        block[MAGIC.len()] = count as u8;
+        disk.write_block(0, &block)
+    }
+
+    // Reads file `index` into `name` and `contents`, returning its length.
+    pub fn read_file<D: BlockDevice<BLOCK_SIZE>>(disk: &mut D, index: usize, name: &mut [u8; NAME_BYTES], contents: &mut [u8]) -> Result<usize, D::Error> {
+        let slot = Self::slot(index);
+        let mut block = [0; BLOCK_SIZE];
+        disk.read_block(slot, &mut block)?;
+        name.copy_from_slice(&block[0..NAME_BYTES]);
+        let len = (u16::from_le_bytes([block[NAME_BYTES], block[NAME_BYTES + 1]]) as usize)
+            .min(contents.len())
+            .min(MAX_FILE_BLOCKS * BLOCK_SIZE);
+        for (i, chunk) in contents[0..len].chunks_mut(BLOCK_SIZE).enumerate() {
+            disk.read_block(slot + 1 + i, &mut block)?;
+            chunk.copy_from_slice(&block[0..chunk.len()]);
+        }
+        Ok(len)
+    }
+
+    // Stores `contents` under `name` as file `index`.
+    pub fn write_file<D: BlockDevice<BLOCK_SIZE>>(disk: &mut D, index: usize, name: &[u8], contents: &[u8]) -> Result<(), D::Error> {
+        let slot = Self::slot(index);
+        let len = contents.len().min(MAX_FILE_BLOCKS * BLOCK_SIZE);
+        let mut block = [0; BLOCK_SIZE];
+        let name_len = name.len().min(NAME_BYTES);
+        block[0..name_len].copy_from_slice(&name[0..name_len]);
+        block[NAME_BYTES..NAME_BYTES + 2].copy_from_slice(&(len as u16).to_le_bytes());
+        disk.write_block(slot, &block)?;
+        for (i, chunk) in contents[0..len].chunks(BLOCK_SIZE).enumerate() {
+            block = [0; BLOCK_SIZE];
+            block[0..chunk.len()].copy_from_slice(chunk);
+            disk.write_block(slot + 1 + i, &block)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(all(test, not(target_os = "none")))]
+mod tests {
+    use super::*;
+
+    type Layout = ImageLayout<16, 2, 4>;
+
+    struct TestDisk {
+        blocks: Vec<[u8; 16]>,
+    }
+
+    impl TestDisk {
+        fn new() -> Self {
+            Self {blocks: vec![[0; 16]; Layout::image_bytes(3) / 16]}
+        }
+    }
+
+    impl BlockDevice<16> for TestDisk {
+        type Error = usize;
+
+        fn read_block(&mut self, block: usize, buffer: &mut [u8; 16]) -> Result<(), usize> {
+            *buffer = *self.blocks.get(block).ok_or(block)?;
+            Ok(())
+        }
+
+        fn write_block(&mut self, block: usize, buffer: &[u8; 16]) -> Result<(), usize> {
+            *self.blocks.get_mut(block).ok_or(block)? = *buffer;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn files_read_back_as_written() {
+        let mut disk = TestDisk::new();
+        Layout::write_file(&mut disk, 0, b"a", b"").unwrap();
+        Layout::write_file(&mut disk, 1, b"prog", &[7; 20]).unwrap();
+        Layout::write_count(&mut disk, 2).unwrap();
+        assert_eq!(Layout::read_count(&mut disk), Ok(Some(2)));
+
+        let mut name = [0; 4];
+        let mut contents = [0; 32];
+        assert_eq!(Layout::read_file(&mut disk, 0, &mut name, &mut contents), Ok(0));
+        assert_eq!(&name, b"a\0\0\0");
+        assert_eq!(Layout::read_file(&mut disk, 1, &mut name, &mut contents), Ok(20));
+        assert_eq!(&name, b"prog");
+        assert_eq!(contents[0..20], [7; 20]);
+    }
+
+    #[test]
+    fn files_longer_than_a_slot_are_cut_short() {
+        let mut disk = TestDisk::new();
+        Layout::write_file(&mut disk, 0, b"big", &[1; 40]).unwrap();
+        Layout::write_file(&mut disk, 1, b"next", &[2; 3]).unwrap();
+        let mut name = [0; 4];
+        let mut contents = [0; 40];
+        assert_eq!(Layout::read_file(&mut disk, 0, &mut name, &mut contents), Ok(32));
+        assert_eq!(Layout::read_file(&mut disk, 1, &mut name, &mut contents), Ok(3));
+        assert_eq!(contents[0..3], [2; 3]);
+    }
+
+    #[test]
+    fn disks_without_the_magic_are_unformatted() {
+        let mut disk = TestDisk::new();
+        assert_eq!(Layout::read_count(&mut disk), Ok(None));
+        Layout::write_count(&mut disk, 1).unwrap();
+        disk.blocks[0][0] = b'X';
+        assert_eq!(Layout::read_count(&mut disk), Ok(None));
+    }
+
+    #[test]
+    fn read_errors_are_passed_on() {
+        let mut disk = TestDisk {blocks: Vec::new()};
+        assert_eq!(Layout::read_count(&mut disk), Err(0));
+    }
+}
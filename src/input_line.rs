@@ -0,0 +1,301 @@
+// The line a program's input is typed into, with a cursor that can move
+// within it, plus the last HISTORY lines entered for recall. The line holds
+// at most CAPACITY characters; further typing is ignored.
+pub struct InputLine<const CAPACITY: usize, const HISTORY: usize> {
+    text: [char; CAPACITY],
+    len: usize,
+    cursor: usize,
+    history: [[char; CAPACITY]; HISTORY],
+    history_lens: [usize; HISTORY],
+    history_start: usize,
+    history_len: usize,
+    // How many entries back from the newest the line was recalled from.
+    recalled: Option<usize>,
+}
+
+impl<const CAPACITY: usize, const HISTORY: usize> InputLine<CAPACITY, HISTORY> {
+    pub fn new() -> Self {
+        Self {
+            text: ['\0'; CAPACITY],
+            len: 0,
+            cursor: 0,
+            history: [['\0'; CAPACITY]; HISTORY],
+            history_lens: [0; HISTORY],
+            history_start: 0,
+            history_len: 0,
+            recalled: None,
+        }
+    }
+
+    pub fn text(&self) -> &[char] {
+        &self.text[0..self.len]
+    }
+
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    // First character to show when only `width` columns are free, so that
+    // the cursor stays in view.
+    pub fn view_start(&self, width: usize) -> usize {
+        (self.cursor + 1).saturating_sub(width)
+    }
+
+    pub fn insert(&mut self, c: char) {
+        if self.len == CAPACITY {
+            return;
+        }
+        self.text.copy_within(self.cursor..self.len, self.cursor + 1);
+        self.text[self.cursor] = c;
+        self.len += 1;
+        self.cursor += 1;
+    }
+
+    pub fn backspace(&mut self) {
+        if self.cursor > 0 {
+            self.cursor -= 1;
+            self.delete();
+        }
+    }
+
+    pub fn delete(&mut self) {
+        if self.cursor < self.len {
+            self.text.copy_within(self.cursor + 1..self.len, self.cursor);
+            self.len -= 1;
+        }
+    }
+
+    pub fn left(&mut self) {
+        self.cursor = self.cursor.saturating_sub(1);
+    }
+
+    pub fn right(&mut self) {
+        self.cursor = (self.cursor + 1).min(self.len);
+    }
+
+    pub fn home(&mut self) {
+        self.cursor = 0;
+    }
+
+    pub fn end(&mut self) {
+        self.cursor = self.len;
+    }
+
+    // Replaces the line with the entry entered before the one showing.
+    pub fn older(&mut self) {
+        let back = self.recalled.map_or(0, |back| back + 1);
+        if back < self.history_len {
+            self.recall(Some(back));
+        }
+    }
+
+    // Replaces the line with the entry entered after the one showing, or an
+    // empty line after the newest.
+    pub fn newer(&mut self) {
+        match self.recalled {
+            Some(0) => self.recall(None),
+            Some(back) => self.recall(Some(back - 1)),
+            None => {}
+        }
+    }
+
+    fn recall(&mut self, back: Option<usize>) {
+        self.recalled = back;
+        self.len = match back {
+            Some(back) => {
+                let i = (self.history_start + self.history_len - 1 - back) % HISTORY;
+                self.text = self.history[i];
+                self.history_lens[i]
+            }
+            None => 0,
+        };
+        self.cursor = self.len;
+    }
+
+    // Keeps the line in the history, dropping the oldest entry once the
+    // history is full, and starts a new empty one. Empty lines and repeats of
+    // the newest entry aren't kept.
+    pub fn submit(&mut self) {
+        if HISTORY > 0 && self.len > 0 && !self.repeats_newest() {
+            let i = if self.history_len < HISTORY {
+                self.history_len += 1;
+                (self.history_start + self.history_len - 1) % HISTORY
+            } else {
+                let oldest = self.history_start;
+                self.history_start = (self.history_start + 1) % HISTORY;
+                oldest
+            };
+            self.history[i] = self.text;
+            self.history_lens[i] = self.len;
+        }
+        self.clear();
+    }
+
+    fn repeats_newest(&self) -> bool {
+        if self.history_len == 0 {
+            return false;
+        }
+        let newest = (self.history_start + self.history_len - 1) % HISTORY;
+        self.history[newest][0..self.history_lens[newest]] == self.text[0..self.len]
+    }
+
+    // Empties the line, keeping the history.
+    pub fn clear(&mut self) {
+        self.len = 0;
+        self.cursor = 0;
+        self.recalled = None;
+    }
+}
+
+// Lines entered for a program before it asked for them, oldest first. Holds
+// up to LINES lines of at most CAPACITY characters.
+pub struct LineQueue<const CAPACITY: usize, const LINES: usize> {
+    lines: [[char; CAPACITY]; LINES],
+    lens: [usize; LINES],
+    start: usize,
+    len: usize,
+}
+
+impl<const CAPACITY: usize, const LINES: usize> LineQueue<CAPACITY, LINES> {
+    pub fn new() -> Self {
+        Self {lines: [['\0'; CAPACITY]; LINES], lens: [0; LINES], start: 0, len: 0}
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    // Adds a line at the back, returning false if the queue is already full.
+    pub fn push(&mut self, line: &[char]) -> bool {
+        if self.len == LINES {
+            return false;
+        }
+        let i = (self.start + self.len) % LINES;
+        let len = line.len().min(CAPACITY);
+        self.lines[i][0..len].copy_from_slice(&line[0..len]);
+        self.lens[i] = len;
+        self.len += 1;
+        true
+    }
+
+    // Takes the line at the front.
+    pub fn pop(&mut self) -> Option<&[char]> {
+        if self.len == 0 {
+            return None;
+        }
+        let i = self.start;
+        self.start = (self.start + 1) % LINES;
+        self.len -= 1;
+        Some(&self.lines[i][0..self.lens[i]])
+    }
+
+    pub fn clear(&mut self) {
+        self.start = 0;
+        self.len = 0;
+    }
+}
+
+#[cfg(all(test, not(target_os = "none")))]
+mod tests {
+    use super::*;
+
+    fn typed(text: &str) -> InputLine<8, 3> {
+        let mut line = InputLine::new();
+        for c in text.chars() {
+            line.insert(c);
+        }
+        line
+    }
+
+    fn text<const C: usize, const H: usize>(line: &InputLine<C, H>) -> String {
+        line.text().iter().collect()
+    }
+
+    #[test]
+    fn editing_happens_at_the_cursor() {
+        let mut line = typed("13");
+        line.left();
+        line.insert('2');
+        assert_eq!(text(&line), "123");
+        line.home();
+        line.delete();
+        line.end();
+        line.backspace();
+        assert_eq!(text(&line), "2");
+        assert_eq!(line.cursor(), 1);
+    }
+
+    #[test]
+    fn typing_past_capacity_is_ignored() {
+        let line = typed("0123456789");
+        assert_eq!(text(&line), "01234567");
+    }
+
+    #[test]
+    fn the_cursor_stays_in_view() {
+        let mut line = typed("0123456");
+        assert_eq!(line.view_start(4), 4);
+        line.home();
+        assert_eq!(line.view_start(4), 0);
+    }
+
+    #[test]
+    fn up_and_down_walk_the_history() {
+        let mut line = typed("one");
+        line.submit();
+        for c in "two".chars() {
+            line.insert(c);
+        }
+        line.submit();
+        assert_eq!(text(&line), "");
+        line.older();
+        assert_eq!(text(&line), "two");
+        line.older();
+        line.older();
+        assert_eq!(text(&line), "one");
+        line.newer();
+        assert_eq!(text(&line), "two");
+        line.newer();
+        assert_eq!(text(&line), "");
+    }
+
+    #[test]
+    fn the_oldest_entries_are_forgotten() {
+        let mut line = typed("");
+        for entry in ["a", "b", "b", "", "c", "d"] {
+            for c in entry.chars() {
+                line.insert(c);
+            }
+            line.submit();
+        }
+        let mut recalled = Vec::new();
+        for _ in 0..4 {
+            line.older();
+            recalled.push(text(&line));
+        }
+        assert_eq!(recalled, ["d", "c", "b", "b"]);
+    }
+
+    fn popped<const C: usize, const L: usize>(queue: &mut LineQueue<C, L>) -> Option<String> {
+        queue.pop().map(|line| line.iter().collect())
+    }
+
+    #[test]
+    fn queued_lines_come_out_in_order() {
+        let mut queue = LineQueue::<8, 2>::new();
+        for line in ["1", "2", "3"] {
+            let chars: Vec<char> = line.chars().collect();
+            assert_eq!(queue.push(&chars), line != "3");
+        }
+        assert_eq!(popped(&mut queue).as_deref(), Some("1"));
+        assert!(queue.push(&['4']));
+        assert_eq!(popped(&mut queue).as_deref(), Some("2"));
+        assert_eq!(popped(&mut queue).as_deref(), Some("4"));
+        assert_eq!(popped(&mut queue), None);
+        assert!(queue.is_empty());
+    }
+}
@@ -0,0 +1,136 @@
+use lazy_static::lazy_static;
+use pc_keyboard::{layouts, HandleControl, Keyboard, ScancodeSet1};
+use pic8259::ChainedPics;
+use spin::Mutex;
+use x86_64::instructions::port::Port;
+use x86_64::instructions::segmentation::{Segment, CS};
+use x86_64::instructions::tables::load_tss;
+use x86_64::registers::control::Cr2;
+use x86_64::structures::gdt::{Descriptor, GlobalDescriptorTable, SegmentSelector};
+use x86_64::structures::idt::{InterruptDescriptorTable, InterruptStackFrame, PageFaultErrorCode};
+use x86_64::structures::tss::TaskStateSegment;
+use x86_64::VirtAddr;
+
+use crate::runtime;
+
+// The CPU exceptions and the two hardware interrupts the kernel uses: the PIT
+// timer and the PS/2 keyboard, remapped past the exceptions to 32 and 33.
+// Exceptions other than a double fault become panics, and so end up on the
+// fault screen.
+
+const PIC_1_OFFSET: u8 = 32;
+const PIC_2_OFFSET: u8 = PIC_1_OFFSET + 8;
+const TIMER: u8 = PIC_1_OFFSET;
+const KEYBOARD: u8 = PIC_1_OFFSET + 1;
+
+const KEYBOARD_DATA: u16 = 0x60;
+
+// A double fault usually means the kernel stack overflowed, so its handler
+// gets a stack of its own.
+const DOUBLE_FAULT_IST_INDEX: u16 = 0;
+const DOUBLE_FAULT_STACK_BYTES: usize = 4096 * 5;
+
+static PICS: Mutex<ChainedPics> = Mutex::new(unsafe { ChainedPics::new(PIC_1_OFFSET, PIC_2_OFFSET) });
+
+struct Selectors {
+    code: SegmentSelector,
+    tss: SegmentSelector,
+}
+
+lazy_static! {
+    static ref TSS: TaskStateSegment = {
+        static mut STACK: [u8; DOUBLE_FAULT_STACK_BYTES] = [0; DOUBLE_FAULT_STACK_BYTES];
+        let mut tss = TaskStateSegment::new();
+        let start = VirtAddr::from_ptr(core::ptr::addr_of!(STACK));
+        tss.interrupt_stack_table[DOUBLE_FAULT_IST_INDEX as usize] = start + DOUBLE_FAULT_STACK_BYTES;
+        tss
+    };
+
+    static ref GDT: (GlobalDescriptorTable, Selectors) = {
+        let mut gdt = GlobalDescriptorTable::new();
+        let code = gdt.add_entry(Descriptor::kernel_code_segment());
+        let tss = gdt.add_entry(Descriptor::tss_segment(&TSS));
+        (gdt, Selectors {code, tss})
+    };
+
+    static ref IDT: InterruptDescriptorTable = {
+        let mut idt = InterruptDescriptorTable::new();
+        idt.breakpoint.set_handler_fn(breakpoint);
+        idt.divide_error.set_handler_fn(divide_error);
+        idt.invalid_opcode.set_handler_fn(invalid_opcode);
+        idt.general_protection_fault.set_handler_fn(general_protection_fault);
+        idt.page_fault.set_handler_fn(page_fault);
+        unsafe {
+            idt.double_fault.set_handler_fn(double_fault).set_stack_index(DOUBLE_FAULT_IST_INDEX);
+        }
+        idt[TIMER as usize].set_handler_fn(timer);
+        idt[KEYBOARD as usize].set_handler_fn(keyboard);
+        idt
+    };
+
+    static ref KEYS: Mutex<Keyboard<layouts::Us104Key, ScancodeSet1>> =
+        Mutex::new(Keyboard::new(layouts::Us104Key, ScancodeSet1, HandleControl::Ignore));
+}
+
+// Loads the tables and starts the interrupts.
+pub fn init() {
+    GDT.0.load();
+    unsafe {
+        CS::set_reg(GDT.1.code);
+        load_tss(GDT.1.tss);
+    }
+    IDT.load();
+    unsafe { PICS.lock().initialize() };
+    x86_64::instructions::interrupts::enable();
+}
+
+// Lets both PICs send interrupts again, in case a panic interrupted a handler
+// before it acknowledged one.
+pub fn acknowledge_all() {
+    unsafe { PICS.lock().notify_end_of_interrupt(PIC_2_OFFSET) };
+}
+
+extern "x86-interrupt" fn timer(_frame: InterruptStackFrame) {
+    runtime::tick();
+    unsafe { PICS.lock().notify_end_of_interrupt(TIMER) };
+}
+
+extern "x86-interrupt" fn keyboard(_frame: InterruptStackFrame) {
+    let scancode: u8 = unsafe { Port::new(KEYBOARD_DATA).read() };
+    let mut keys = KEYS.lock();
+    if let Ok(Some(event)) = keys.add_byte(scancode) {
+        if let Some(key) = keys.process_keyevent(event) {
+            runtime::key(key);
+        }
+    }
+    unsafe { PICS.lock().notify_end_of_interrupt(KEYBOARD) };
+}
+
+extern "x86-interrupt" fn breakpoint(frame: InterruptStackFrame) {
+    serial_println!("[kernel] breakpoint\n{:#?}", frame);
+}
+
+extern "x86-interrupt" fn divide_error(frame: InterruptStackFrame) {
+    panic!("divide error at {:?}", frame.instruction_pointer);
+}
+
+extern "x86-interrupt" fn invalid_opcode(frame: InterruptStackFrame) {
+    panic!("invalid opcode at {:?}", frame.instruction_pointer);
+}
+
+extern "x86-interrupt" fn general_protection_fault(frame: InterruptStackFrame, code: u64) {
+    panic!("general protection fault ({}) at {:?}", code, frame.instruction_pointer);
+}
+
+extern "x86-interrupt" fn page_fault(frame: InterruptStackFrame, code: PageFaultErrorCode) {
+    panic!("page fault at {:?} ({:?}) from {:?}", Cr2::read(), code, frame.instruction_pointer);
+}
+
+// The fault screen needs more stack than this handler has, so it only
+// reports and halts.
+extern "x86-interrupt" fn double_fault(frame: InterruptStackFrame, _code: u64) -> ! {
+    serial_println!("[kernel] double fault; halting\n{:#?}", frame);
+    loop {
+        x86_64::instructions::hlt();
+    }
+}
@@ -1,25 +1,57 @@
-#![no_std]
+#![cfg_attr(target_os = "none", no_std)]
+#![cfg_attr(target_os = "none", feature(abi_x86_interrupt))]
 #![feature(prelude_2024)]
 
-use filesystem::FileSystem;
+use filesystem::{FileSystem, FileSystemResult};
 use gc_heap::CopyingHeap;
 // use file_system_solution::{FileSystem, FileSystemResult};
 use pc_keyboard::{DecodedKey, KeyCode};
-use pluggable_interrupt_os::{println, print};
-use pluggable_interrupt_os::vga_buffer::{BUFFER_WIDTH, BUFFER_HEIGHT, plot, ColorCode, Color, plot_str, is_drawable, plot_num};
 use ramdisk::RamDisk;
-use simple_interp::{Interpreter, InterpreterOutput, i64_into_buffer, TickResult};
+use simple_interp::{Interpreter, InterpreterOutput, TickResult};
 // use gc_heap::CopyingHeap;
 
 // Get rid of some spurious VSCode errors
 use core::option::Option;
 use core::option::Option::None;
-use core::panic;
 use core::prelude::rust_2024::derive;
 use core::clone::Clone;
 use core::cmp::{PartialEq,Eq};
 use core::marker::Copy;
 use core::str::from_utf8;
+use core::ops::Range;
+use core::fmt::{Debug, Write};
+use core::mem::MaybeUninit;
+use core::ptr::addr_of_mut;
+
+// Only bare-metal builds open the disk, so host builds leave parts unused.
+#[cfg_attr(not(target_os = "none"), allow(dead_code))]
+mod ata;
+#[macro_use]
+pub mod serial;
+pub mod display;
+mod editor;
+mod fault;
+mod input_line;
+#[cfg(target_os = "none")]
+mod interrupts;
+mod output_log;
+#[cfg(target_os = "none")]
+pub mod runtime;
+mod scheduler;
+mod shell;
+#[cfg(target_os = "none")]
+pub mod testing;
+#[cfg_attr(not(target_os = "none"), allow(dead_code))]
+mod image;
+use ata::AtaDisk;
+use display::{BUFFER_WIDTH, BUFFER_HEIGHT, ColorCode, Color, is_drawable, Display, MemoryDisplay};
+use editor::Editor;
+use fault::{Fault, OrFault};
+use input_line::{InputLine, LineQueue};
+use output_log::OutputLog;
+use scheduler::Scheduler;
+use shell::{Command, ParseError};
+use image::{BlockDevice, ImageLayout};
 
 const FIRST_BORDER_ROW: usize = 1;
 const LAST_BORDER_ROW: usize = BUFFER_HEIGHT - 1;
@@ -31,8 +63,35 @@ const WINDOW_HEIGHT: usize = (LAST_BORDER_ROW - FIRST_BORDER_ROW - 2) / 2;
 const MID_WIDTH: usize = WINDOWS_WIDTH / 2;
 const MID_HEIGHT: usize = BUFFER_HEIGHT / 2;
 const NUM_WINDOWS: usize = 4;
+// Programs running in the background, off screen. The task manager has room
+// below the windows' blocks to list this many.
+const MAX_JOBS: usize = 4;
+const NUM_PROCESSES: usize = NUM_WINDOWS + MAX_JOBS;
+const FILE_ROWS: usize = WINDOW_HEIGHT - FIRST_BORDER_ROW - 1;
+const FILE_COLUMNS: usize = 3;
+const SCROLLBACK_LINES: usize = 100;
+const SCROLLBACK_WIDTH: usize = MID_WIDTH - 1;
+// Interpreter ticks a window gets in a row before the next one's turn.
+const QUANTUM: usize = 4;
+// Rows of the task manager given to each window.
+const TASK_ROWS: usize = 5;
+
+// Longest line a program can be given as input, how many earlier lines each
+// window keeps for recall with the up arrow, and how many lines can be typed
+// ahead of a program asking for them.
+const MAX_INPUT_CHARS: usize = 128;
+const INPUT_HISTORY: usize = 10;
+const MAX_QUEUED_LINES: usize = 8;
+
+// Output kept per program, to show again when a background job is attached
+// to a window.
+const LOG_BYTES: usize = 2048;
+
+// Blank rows and columns around the report on the fault screen.
+const PAGE_MARGIN: usize = 2;
 
 const FILENAME_PROMPT: &str = "F5 - Filename: ";
+const SHELL_PROMPT: &[u8] = b"$ ";
 
 const MAX_OPEN: usize = 16;
 const BLOCK_SIZE: usize = 256;
@@ -56,115 +115,403 @@ const MAX_HEAP_BLOCKS: usize = HEAP_SIZE;
 // Interpreter<MAX_TOKENS, MAX_LITERAL_CHARS, STACK_DEPTH, MAX_LOCAL_VARS, WINDOW_WIDTH, CopyingHeap<HEAP_SIZE, MAX_HEAP_BLOCKS>>
 
 
-pub struct Kernel {
+
+type WindowInterpreter = Interpreter<MAX_TOKENS, MAX_LITERAL_CHARS, STACK_DEPTH, MAX_LOCAL_VARS, WINDOW_WIDTH, CopyingHeap<HEAP_SIZE, MAX_HEAP_BLOCKS>>;
+type KernelFileSystem = FileSystem<MAX_OPEN, BLOCK_SIZE, NUM_BLOCKS, MAX_FILE_BLOCKS, MAX_FILE_BYTES, MAX_FILES_STORED, MAX_FILENAME_BYTES>;
+// The file count, the directory and each file's size, for `ls`.
+type SizedFiles = (usize, [[u8; MAX_FILENAME_BYTES]; MAX_FILES_STORED], [usize; MAX_FILES_STORED]);
+type DiskLayout = ImageLayout<BLOCK_SIZE, MAX_FILE_BLOCKS, MAX_FILENAME_BYTES>;
+type WindowInput = InputLine<MAX_INPUT_CHARS, INPUT_HISTORY>;
+type InputQueue = LineQueue<MAX_INPUT_CHARS, MAX_QUEUED_LINES>;
+type ProcessLog = OutputLog<LOG_BYTES>;
+
+pub struct Kernel<D: Display> {
+    display: D,
     screen : [[char; BUFFER_WIDTH]; BUFFER_HEIGHT],
     process_info : [[char; TASK_MANAGER_WIDTH]; BUFFER_HEIGHT],
+    // What the top row showed before a prompt or notice covered it.
     file_entry : [char; BUFFER_WIDTH],
     active : usize,
-    files: FileSystem<MAX_OPEN, BLOCK_SIZE, NUM_BLOCKS, MAX_FILE_BLOCKS, MAX_FILE_BYTES, MAX_FILES_STORED, MAX_FILENAME_BYTES>,
+    files: KernelFileSystem,
     file_count : usize,
     def_buffer : [char; MAX_FILENAME_BYTES + 1],
-    q1_buffer : [char; MAX_FILENAME_BYTES + 1],
-    q2_buffer : [char; MAX_FILENAME_BYTES + 1],
-    q3_buffer : [char; MAX_FILENAME_BYTES + 1],
-    q4_buffer : [char; MAX_FILENAME_BYTES + 1],
     buffer_offset : usize,
+    file_scroll : usize,
+    show_sizes : bool,
+    file_sizes : [usize; MAX_FILES_STORED],
+    blocks_used : usize,
+    // Whether the files changed since file_sizes and blocks_used were
+    // measured.
+    usage_stale : bool,
     editing : bool,
-    new_line : bool,
+    windows: [Process; NUM_WINDOWS],
+    jobs: [Process; MAX_JOBS],
+    // The background job F11 picked for F7-F9 to act on, if any.
+    selected_job: Option<usize>,
+    next_pid: usize,
+    scheduler: Scheduler<NUM_PROCESSES>,
+    editor: Editor<MAX_FILE_BYTES>,
+    file_prompt: Option<FilePrompt>,
+    disk: Option<AtaDisk>,
+    fault: Option<Fault>,
+    restart_requested: bool,
+    // A message is on the top row, to be cleared by the next key.
+    notice: bool,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum PromptKind {
+    Delete,
+    Rename,
+    Attach,
+}
+
+// A question asked on the top row, about one file or about which job to
+// attach. `start` is the column
+// where the answer is typed, and `window` the window it was asked from.
+#[derive(Clone, Copy)]
+struct FilePrompt {
+    kind: PromptKind,
+    target: [u8; MAX_FILENAME_BYTES],
+    start: usize,
+    window: usize,
+}
+
+// Where a program runs: in one of the windows (F1-F4), or off screen as a
+// background job known by its process ID.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Place {
+    Window(usize),
+    Job(usize),
+}
+
+impl core::fmt::Display for Place {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            Place::Window(window) => write!(f, "F{}", window),
+            Place::Job(pid) => write!(f, "#{}", pid),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ProcessState {
+    Idle,
+    Running,
+    Waiting,
+    Paused,
+    Done,
+    Error,
+    Shell,
+}
+
+impl ProcessState {
+    fn name(self) -> &'static str {
+        match self {
+            ProcessState::Idle => "idle",
+            ProcessState::Running => "running",
+            ProcessState::Waiting => "waiting",
+            ProcessState::Paused => "paused",
+            ProcessState::Done => "done",
+            ProcessState::Error => "error",
+            ProcessState::Shell => "shell",
+        }
+    }
+}
+
+// Everything a single window (F1-F4) or background job owns: the file
+// highlighted in it, the interpreter running in it, the line being typed for
+// that interpreter and the lines typed ahead of it asking for them. Each
+// program launched gets a new process ID. A window in shell mode uses the
+// line and its console for commands instead.
+struct Process {
+    filename: [char; MAX_FILENAME_BYTES + 1],
+    pid: usize,
+    interp: WindowInterpreter,
     running: bool,
     waiting: bool,
-    input1: [char; 20],
-    input_offset1: usize,
-    input_flag1 : bool,
-    input2: [char; 20],
-    input_offset2: usize,
-    input_flag2 : bool,
-    input3: [char; 20],
-    input_offset3: usize,
-    input_flag3 : bool,
-    q1_run: (bool, bool, bool),
-    q1_int : Interpreter<MAX_TOKENS, MAX_LITERAL_CHARS, STACK_DEPTH, MAX_LOCAL_VARS, WINDOW_WIDTH, CopyingHeap<HEAP_SIZE, MAX_HEAP_BLOCKS>>,
-    q2_run: (bool, bool, bool),
-    q2_int : Interpreter<MAX_TOKENS, MAX_LITERAL_CHARS, STACK_DEPTH, MAX_LOCAL_VARS, WINDOW_WIDTH, CopyingHeap<HEAP_SIZE, MAX_HEAP_BLOCKS>>,
-    q3_run: (bool, bool, bool),
-    q3_int : Interpreter<MAX_TOKENS, MAX_LITERAL_CHARS, STACK_DEPTH, MAX_LOCAL_VARS, WINDOW_WIDTH, CopyingHeap<HEAP_SIZE, MAX_HEAP_BLOCKS>>,
-    q4_run: (bool, bool, bool),
-    //q4_int : Interpreter<MAX_TOKENS, MAX_LITERAL_CHARS, STACK_DEPTH, MAX_LOCAL_VARS, WINDOW_WIDTH, CopyingHeap<HEAP_SIZE, MAX_HEAP_BLOCKS>>,
-    ticks: [usize; 4],
-    turn: [bool; 4],
-    turn_index: usize,
-    new_line1: bool,
-    new_line2: bool,
-    new_line3: bool,
-    // YOUR CODE HERE
+    launched: bool,
+    paused: bool,
+    failed: bool,
+    shell: bool,
+    input: WindowInput,
+    queue: InputQueue,
+    console: Console,
+    ticks: usize,
+}
+
+impl Process {
+    fn new() -> Self {
+        Self {
+            filename: [' '; MAX_FILENAME_BYTES + 1],
+            pid: 0,
+            interp: Interpreter::new(""),
+            running: false,
+            waiting: false,
+            launched: false,
+            paused: false,
+            failed: false,
+            shell: false,
+            input: InputLine::new(),
+            queue: LineQueue::new(),
+            console: Console::new(),
+            ticks: 0,
+        }
+    }
+
+    fn filename_bytes(&self) -> [u8; MAX_FILENAME_BYTES] {
+        core::array::from_fn(|i| self.filename[i] as u8)
+    }
+
+    fn filename_len(&self) -> usize {
+        self.filename.iter().take_while(|c| **c != '\0').count()
+    }
+
+    // Whether typing goes to this process's input line.
+    fn takes_input(&self) -> bool {
+        self.running || self.shell
+    }
+
+    fn state(&self) -> ProcessState {
+        if self.shell {
+            ProcessState::Shell
+        } else if self.failed {
+            ProcessState::Error
+        } else if !self.launched {
+            ProcessState::Idle
+        } else if !self.running {
+            ProcessState::Done
+        } else if self.paused {
+            ProcessState::Paused
+        } else if self.waiting {
+            ProcessState::Waiting
+        } else {
+            ProcessState::Running
+        }
+    }
+
+    fn stop(&mut self) {
+        self.running = false;
+        self.waiting = false;
+        self.launched = false;
+        self.paused = false;
+        self.failed = false;
+        self.shell = false;
+        self.input.clear();
+        self.queue.clear();
+        self.console = Console::new();
+        self.ticks = 0;
+    }
+}
+
+// Where a window's program output goes next, plus the rows that have
+// scrolled off the top of the window and the output so far.
+struct Console {
+    cursor: (usize, usize),
+    new_line: bool,
+    history: [[u8; SCROLLBACK_WIDTH]; SCROLLBACK_LINES],
+    history_start: usize,
+    history_len: usize,
+    view_offset: usize,
+    serial_line: bool,
+    log: ProcessLog,
+}
+
+impl Console {
+    fn new() -> Self {
+        Self {
+            cursor: (0, 0),
+            new_line: false,
+            history: [[b' '; SCROLLBACK_WIDTH]; SCROLLBACK_LINES],
+            history_start: 0,
+            history_len: 0,
+            view_offset: 0,
+            serial_line: false,
+            log: OutputLog::new(),
+        }
+    }
+
+    // Keeps a row that is about to scroll away, dropping the oldest one once
+    // the history is full. A scrolled-back view stays on the same rows.
+    fn push_history(&mut self, row: &[char]) {
+        let mut line = [b' '; SCROLLBACK_WIDTH];
+        for (i, c) in row.iter().take(SCROLLBACK_WIDTH).enumerate() {
+            line[i] = *c as u8;
+        }
+        if self.history_len < SCROLLBACK_LINES {
+            self.history[(self.history_start + self.history_len) % SCROLLBACK_LINES] = line;
+            self.history_len += 1;
+        } else {
+            self.history[self.history_start] = line;
+            self.history_start = (self.history_start + 1) % SCROLLBACK_LINES;
+        }
+        if self.view_offset > 0 {
+            self.view_offset = (self.view_offset + 1).min(self.history_len);
+        }
+    }
+
+    // The `i`th oldest row of history.
+    fn history_line(&self, i: usize) -> &[u8; SCROLLBACK_WIDTH] {
+        &self.history[(self.history_start + i) % SCROLLBACK_LINES]
+    }
+
+    fn page_up(&mut self, rows: usize) {
+        self.view_offset = (self.view_offset + rows).min(self.history_len);
+    }
+
+    fn page_down(&mut self, rows: usize) {
+        self.view_offset = self.view_offset.saturating_sub(rows);
+    }
+}
+
+// Border row that holds the header of a window.
+fn header_row(window: usize) -> usize {
+    if window <= 2 {FIRST_BORDER_ROW} else {MID_HEIGHT}
+}
+
+// Where a window's header says it is paused.
+fn paused_col(window: usize) -> usize {
+    border_col(window) + WINDOW_WIDTH / 2 + 8
 }
 
-const HELLO: &str = r#"print("Hello, world!")"#;
+// Border column on the left-hand side of a window.
+fn border_col(window: usize) -> usize {
+    if window % 2 == 1 {0} else {MID_WIDTH}
+}
 
-const NUMS: &str = r#"print(1)
-print(257)"#;
+// Screen rows inside a window's borders.
+fn window_rows(window: usize) -> Range<usize> {
+    let bottom = if window <= 2 {MID_HEIGHT} else {LAST_BORDER_ROW};
+    header_row(window) + 1..bottom
+}
 
-const ADD_ONE: &str = r#"x := input("Enter a number")
-x := (x + 1)
-print(x)"#;
+// Screen columns inside a window's borders.
+fn window_cols(window: usize) -> Range<usize> {
+    border_col(window) + 1..border_col(window) + MID_WIDTH
+}
 
-const COUNTDOWN: &str = r#"count := input("count")
-while (count > 0) {
-    count := (count - 1)
+// Screen position of the `index`th entry of the file grid in a window.
+fn file_spot(window: usize, index: usize, columns: usize) -> (usize, usize) {
+    (window_rows(window).start + index / columns, window_cols(window).start + index % columns * (MAX_FILENAME_BYTES + 1))
 }
-print("done")
-print(count)"#;
-
-const AVERAGE: &str = r#"sum := 0
-count := 0
-averaging := true
-while averaging {
-    num := input("Enter a number:")
-    if (num == "quit") {
-        averaging := false
+
+// Tick counts in at most five characters: exact up to 9999, then in
+// thousands (k) and millions (M).
+fn write_ticks(writer: &mut impl Write, ticks: usize) -> core::fmt::Result {
+    if ticks < 10_000 {
+        write!(writer, "{}", ticks)
+    } else if ticks < 10_000_000 {
+        write!(writer, "{}k", ticks / 1_000)
     } else {
-        sum := (sum + num)
-        count := (count + 1)
+        write!(writer, "{}M", ticks / 1_000_000)
+    }
+}
+
+// Lets `write!` fill a stretch of a screen row, dropping whatever doesn't fit.
+struct RowWriter<'a> {
+    cells: &'a mut [char],
+    col: usize,
+}
+
+impl <'a> RowWriter<'a> {
+    fn new(cells: &'a mut [char]) -> Self {
+        Self {cells, col: 0}
     }
 }
-print((sum / count))"#;
-
-const PI: &str = r#"sum := 0
-i := 0
-neg := false
-terms := input("Num terms:")
-while (i < terms) {
-    term := (1.0 / ((2.0 * i) + 1.0))
-    if neg {
-        term := -term
-    }
-    sum := (sum + term)
-    neg := not neg
-    i := (i + 1)
+
+impl Write for RowWriter<'_> {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        for c in s.chars() {
+            if self.col < self.cells.len() {
+                self.cells[self.col] = c;
+                self.col += 1;
+            }
+        }
+        Ok(())
+    }
 }
-print((4 * sum))"#;
 
+// Lets `write!` fill a whole screen a line per row, inside a margin of
+// PAGE_MARGIN, dropping whatever doesn't fit.
+struct PageWriter<'a> {
+    screen: &'a mut [[char; BUFFER_WIDTH]; BUFFER_HEIGHT],
+    row: usize,
+    col: usize,
+}
 
+impl <'a> PageWriter<'a> {
+    fn new(screen: &'a mut [[char; BUFFER_WIDTH]; BUFFER_HEIGHT]) -> Self {
+        Self {screen, row: PAGE_MARGIN, col: PAGE_MARGIN}
+    }
+}
 
-fn initial_files(disk: &mut FileSystem<MAX_OPEN, BLOCK_SIZE, NUM_BLOCKS, MAX_FILE_BLOCKS, MAX_FILE_BYTES, MAX_FILES_STORED, MAX_FILENAME_BYTES>) {
-    let mut count = 0;
-    for (filename, contents) in [
-        ("hello", HELLO),
-        ("nums", NUMS),
-        ("add_one", ADD_ONE),
-        ("countdown", COUNTDOWN),
-        ("average", AVERAGE),
-        ("pi", PI),
-    ] {
-    
-        let fd = disk.open_create(filename).unwrap();
-        disk.write(fd, contents.as_bytes()).unwrap();
-        disk.close(fd);
-        
-        
-        count += 1
+impl Write for PageWriter<'_> {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        for c in s.chars() {
+            if c == '\n' {
+                self.row += 1;
+                self.col = PAGE_MARGIN;
+            } else if self.row < BUFFER_HEIGHT - PAGE_MARGIN && self.col < BUFFER_WIDTH - PAGE_MARGIN {
+                self.screen[self.row][self.col] = c;
+                self.col += 1;
+            }
+        }
+        Ok(())
     }
-    
+}
+
+// A directory entry without its '\0' padding.
+fn trim_filename(name: &[u8]) -> &[u8] {
+    let len = name.iter().position(|c| *c == 0).unwrap_or(name.len());
+    &name[0..len]
+}
+
+// Moves everything inside a window up one row and blanks the bottom row.
+fn scroll_window(screen: &mut [[char; BUFFER_WIDTH]; BUFFER_HEIGHT], window: usize) {
+    let rows = window_rows(window);
+    let cols = window_cols(window);
+    for i in rows.start..rows.end - 1 {
+        for j in cols.clone() {
+            screen[i][j] = screen[i + 1][j];
+        }
+    }
+    for j in cols {
+        screen[rows.end - 1][j] = ' ';
+    }
+}
+
+// The files in programs/, as (filename, contents) pairs generated by build.rs.
+const PROGRAMS: &[(&str, &str)] = include!(concat!(env!("OUT_DIR"), "/programs.rs"));
+
+fn initial_files(disk: &mut KernelFileSystem) -> Result<(), Fault> {
+    for (filename, contents) in PROGRAMS {
+        let fd = disk.open_create(filename).or_fault("creating a built-in program")?;
+        disk.write(fd, contents.as_bytes()).or_fault("writing a built-in program")?;
+        disk.close(fd).or_fault("writing a built-in program")?;
+    }
+    Ok(())
+}
+
+// Copies the files saved on a persistent disk into the file system. Returns
+// false if the disk has never been formatted. A disk that can't be read is a
+// fault rather than unformatted, as formatting it would lose the files.
+fn load_files<B: BlockDevice<BLOCK_SIZE>>(disk: &mut B, files: &mut KernelFileSystem) -> Result<bool, Fault> where B::Error: Debug {
+    let count = match DiskLayout::read_count(disk).or_fault("reading the disk")? {
+        Some(count) => count,
+        None => return Ok(false),
+    };
+    let mut name = [0; MAX_FILENAME_BYTES];
+    let mut contents = [0; MAX_FILE_BYTES];
+    for i in 0..count.min(MAX_FILES_STORED) {
+        let len = DiskLayout::read_file(disk, i, &mut name, &mut contents).or_fault("reading a file from disk")?;
+        let filename = from_utf8(trim_filename(&name)).or_fault("reading a filename from disk")?;
+        let fd = files.open_create(filename).or_fault("loading a file from disk")?;
+        if len > 0 {
+            files.write(fd, &contents[0..len]).or_fault("loading a file from disk")?;
+        }
+        files.close(fd).or_fault("loading a file from disk")?;
+    }
+    Ok(true)
 }
 
 pub fn split_screen (mut screen : [[char; BUFFER_WIDTH]; BUFFER_HEIGHT]) -> [[char; BUFFER_WIDTH]; BUFFER_HEIGHT] {
@@ -241,1297 +588,1839 @@ pub fn update_screen(mut screen: [[char; BUFFER_WIDTH]; BUFFER_HEIGHT], num: usi
     return screen
 }
 
-impl Kernel {
-    pub fn new() -> Self {
-        let mut screen = [[' '; BUFFER_WIDTH]; BUFFER_HEIGHT];
-        let mut files: FileSystem<MAX_OPEN, BLOCK_SIZE, NUM_BLOCKS, MAX_FILE_BLOCKS, MAX_FILE_BYTES, MAX_FILES_STORED, MAX_FILENAME_BYTES> = filesystem::FileSystem::new(RamDisk::new()); 
-        let process_info= [[' '; TASK_MANAGER_WIDTH]; BUFFER_HEIGHT];
-        let active = 1;
-        let file_count = 0;
-        initial_files(&mut files);
-        screen = split_screen(screen);
-        let file_entry = screen[0];
-        let def_buffer = [' '; MAX_FILENAME_BYTES + 1];
-        let q1_buffer = [' '; MAX_FILENAME_BYTES + 1];
-        let q2_buffer = [' '; MAX_FILENAME_BYTES + 1];
-        let q3_buffer = [' '; MAX_FILENAME_BYTES + 1];
-        let q4_buffer = [' '; MAX_FILENAME_BYTES + 1];
-        let buffer_offset = 0;
-        let editing = false;
-        let new_line = false;
-        let running = false;
-        let waiting = false;
-        let input1 = ['\0'; 20];
-        let input_offset1 = 0;
-        let input_flag1 = false;
-        let input2 = ['\0'; 20];
-        let input_offset2 = 0;
-        let input_flag2 = false;
-        let input3 = ['\0'; 20];
-        let input_offset3 = 0;
-        let input_flag3 = false;
-        let q1_run = (false, false, false);
-        let q1_int = Interpreter::new("");
-        let q2_run = (false, false, false);
-        let q2_int = Interpreter::new("");
-        let q3_run = (false, false, false);
-        let q3_int = Interpreter::new("");
-        let q4_run = (false, false, false);
-        let ticks = [0; 4];
-        let turn = [false; 4];
-        let turn_index = 1;
-        let new_line1 = false;
-        let new_line2 = false;
-        let new_line3= false;
-        //let q4_int = Interpreter::new("");
-
-        Self{screen, process_info, file_entry, active, files, file_count, q1_buffer ,q2_buffer,q3_buffer,q4_buffer, buffer_offset,def_buffer, editing, new_line, running, waiting, input1, input_offset1, q1_run, q1_int, input_flag1, q2_run, q2_int, q3_run, q3_int, q4_run, ticks , turn, turn_index, new_line1, new_line2, new_line3, input2, input_offset2, input_flag2, input3, input_offset3, input_flag3 } //,q4_int}
-        //todo!("Create your kernel object");
+// Writes `count` new processes from `first` on, one at a time so that only
+// one is ever on the stack.
+unsafe fn write_processes(first: *mut Process, count: usize) {
+    for i in 0..count {
+        first.add(i).write(Process::new());
+    }
+}
+
+// The persistent disk, if one is attached and can hold every file.
+#[cfg(target_os = "none")]
+fn open_disk() -> Option<AtaDisk> {
+    AtaDisk::open().filter(|disk| disk.capacity() >= DiskLayout::image_bytes(MAX_FILES_STORED))
+}
+
+impl<D: Display> Kernel<D> {
+    // Builds a kernel drawing on `display`, with no persistent disk, in
+    // `slot`. This is how the tests run it.
+    pub fn init_in(slot: &mut MaybeUninit<Self>, display: D) -> &mut Self {
+        Self::init_with_disk(slot, display, None)
+    }
+
+    pub fn display(&self) -> &D {
+        &self.display
+    }
+
+    // The kernel is built where it will stay, one field at a time, since
+    // with its processes and file system it is too big to build on the stack
+    // and then move.
+    fn init_with_disk(slot: &mut MaybeUninit<Self>, display: D, disk: Option<AtaDisk>) -> &mut Self {
+        let kernel = slot.as_mut_ptr();
+        unsafe {
+            addr_of_mut!((*kernel).display).write(display);
+            addr_of_mut!((*kernel).disk).write(disk);
+            Self::write_fresh(kernel);
+        }
+        // Every field has now been written.
+        let kernel = unsafe { slot.assume_init_mut() };
+        let result = kernel.load_files();
+        kernel.catch(result);
+        kernel
+    }
+
+    // Starts the kernel over where it is, keeping its display and disk, and
+    // reloads the files.
+    pub fn restart(&mut self) {
+        unsafe { Self::write_fresh(self) };
+        let result = self.load_files();
+        self.catch(result);
+    }
+
+    // Writes every field but the display and disk as they are at boot.
+    // Whatever was there is overwritten without being dropped, which only
+    // matters for fields that own something, and none do.
+    unsafe fn write_fresh(kernel: *mut Self) {
+        let screen = split_screen([[' '; BUFFER_WIDTH]; BUFFER_HEIGHT]);
+        addr_of_mut!((*kernel).screen).write(screen);
+        addr_of_mut!((*kernel).process_info).write([[' '; TASK_MANAGER_WIDTH]; BUFFER_HEIGHT]);
+        addr_of_mut!((*kernel).file_entry).write(screen[0]);
+        addr_of_mut!((*kernel).active).write(1);
+        addr_of_mut!((*kernel).files).write(filesystem::FileSystem::new(RamDisk::new()));
+        addr_of_mut!((*kernel).file_count).write(0);
+        addr_of_mut!((*kernel).def_buffer).write([' '; MAX_FILENAME_BYTES + 1]);
+        addr_of_mut!((*kernel).buffer_offset).write(0);
+        addr_of_mut!((*kernel).file_scroll).write(0);
+        addr_of_mut!((*kernel).show_sizes).write(false);
+        addr_of_mut!((*kernel).file_sizes).write([0; MAX_FILES_STORED]);
+        addr_of_mut!((*kernel).blocks_used).write(0);
+        addr_of_mut!((*kernel).usage_stale).write(true);
+        addr_of_mut!((*kernel).editing).write(false);
+        write_processes(addr_of_mut!((*kernel).windows).cast(), NUM_WINDOWS);
+        write_processes(addr_of_mut!((*kernel).jobs).cast(), MAX_JOBS);
+        addr_of_mut!((*kernel).selected_job).write(None);
+        addr_of_mut!((*kernel).next_pid).write(1);
+        addr_of_mut!((*kernel).scheduler).write(Scheduler::new(QUANTUM));
+        addr_of_mut!((*kernel).editor).write(Editor::new());
+        addr_of_mut!((*kernel).file_prompt).write(None);
+        addr_of_mut!((*kernel).fault).write(None);
+        addr_of_mut!((*kernel).restart_requested).write(false);
+        addr_of_mut!((*kernel).notice).write(false);
+    }
+
+    // Fills the file system from the persistent disk or, when there is no
+    // formatted disk, with the built-in programs, which are then saved.
+    fn load_files(&mut self) -> Result<(), Fault> {
+        let mounted = match self.disk.as_mut() {
+            Some(disk) => load_files(disk, &mut self.files)?,
+            None => false,
+        };
+        match (&self.disk, mounted) {
+            (Some(_), true) => serial_println!("[kernel] loaded files from disk"),
+            (Some(_), false) => serial_println!("[kernel] formatting disk"),
+            (None, _) => serial_println!("[kernel] no disk attached; files will not be saved"),
+        }
+        if !mounted {
+            initial_files(&mut self.files)?;
+            self.sync_disk(0..MAX_FILES_STORED)?;
+        }
+        Ok(())
+    }
+
+    // Writes the files at `range` in directory order, and the file count, out
+    // to the persistent disk, if there is one, so that they survive a reboot.
+    // The other files' slots are left as they are. A disk that fails a write
+    // is not used again. Every change to the files comes through here, so the
+    // disk usage is marked to be measured again too.
+    fn sync_disk(&mut self, range: Range<usize>) -> Result<(), Fault> {
+        self.usage_stale = true;
+        if let Some(mut disk) = self.disk.take() {
+            let (count, filenames) = self.files.list_directory().or_fault("listing files")?;
+            let mut contents = [0; MAX_FILE_BYTES];
+            let mut result = Ok(());
+            for (f, filename) in filenames.iter().enumerate().take(range.end.min(count)).skip(range.start) {
+                let name = trim_filename(filename);
+                let len = self.read_file(from_utf8(name).or_fault("listing files")?, &mut contents)?;
+                result = result.and_then(|_| DiskLayout::write_file(&mut disk, f, name, &contents[0..len]));
+            }
+            result = result.and_then(|_| DiskLayout::write_count(&mut disk, count));
+            match result {
+                Ok(()) => self.disk = Some(disk),
+                Err(e) => serial_println!("[kernel] disk write failed ({:?}); files will not be saved", e),
+            }
+        }
+        Ok(())
+    }
+
+    // Writes out just the file called `name`, after it was saved or created.
+    fn sync_file(&mut self, name: &[u8]) -> Result<(), Fault> {
+        let (count, filenames) = self.files.list_directory().or_fault("listing files")?;
+        match filenames[0..count].iter().position(|f| trim_filename(f) == trim_filename(name)) {
+            Some(f) => self.sync_disk(f..f + 1),
+            None => Ok(()),
+        }
     }
 
+    // On the fault screen the only key that does anything is R, which asks
+    // for the kernel to be restarted.
     pub fn key(&mut self, key: DecodedKey) {
-        match key {
+        if self.fault.is_some() {
+            if matches!(key, DecodedKey::Unicode('r' | 'R')) {
+                serial_println!("[kernel] restarting");
+                self.restart_requested = true;
+            }
+            return;
+        }
+        if self.notice {
+            self.notice = false;
+            self.uncover_top_row();
+        }
+        let result = match key {
             DecodedKey::RawKey(code) => self.handle_raw(code),
             DecodedKey::Unicode(c) => self.handle_unicode(c)
-        }
+        };
+        self.catch(result);
         self.draw();
     }
-    
-    fn update_active(&mut self, num: usize) {
-        
+
+    // Whether R has been pressed on the fault screen, for the caller to
+    // restart the kernel.
+    pub fn wants_restart(&self) -> bool {
+        self.restart_requested
+    }
+
+    // Shows the fault screen for a fault from outside the kernel's own
+    // code, like a panic.
+    #[cfg_attr(not(target_os = "none"), allow(dead_code))]
+    pub(crate) fn fail(&mut self, fault: Fault) {
+        self.catch(Err(fault));
+        self.draw();
+    }
+
+    // Puts the kernel on the fault screen, unless it is already showing an
+    // earlier fault that this one is likely a knock-on effect of.
+    fn catch(&mut self, result: Result<(), Fault>) {
+        if let Err(fault) = result {
+            if self.fault.is_none() {
+                self.fault = Some(fault);
+                self.write_fault_screen();
+            }
+        }
+    }
+
+    fn active_window(&self) -> Option<usize> {
+        if self.active >= 1 && self.active <= NUM_WINDOWS {
+            Some(self.active)
+        } else {
+            None
+        }
+    }
+
+    fn update_active(&mut self, num: usize) -> Result<(), Fault> {
+
         if self.active != num && !self.editing{
             self.active = num;
+            self.selected_job = None;
             self.reset_buffers();
             self.buffer_offset = 0;
+            self.file_scroll = 0;
             self.screen = update_screen(self.screen, num);
-            
+            self.add_files(true)?;
+
         }
-        
+        Ok(())
     }
-    
+
     fn reset_buffers(&mut self) {
-        self.q1_buffer = self.def_buffer;
-        self.q2_buffer = self.def_buffer;
-        self.q3_buffer = self.def_buffer;
-        self.q4_buffer = self.def_buffer;
+        for process in self.windows.iter_mut() {
+            if !process.launched {
+                process.filename = self.def_buffer;
+            }
+        }
     }
-    
-    fn add_files(&mut self, editing: bool ) {
-        let directory = self.files.list_directory().unwrap();
+
+    // Windows that are showing the file grid rather than a program, a shell
+    // or the editor.
+    fn shows_files(&self, window: usize) -> bool {
+        let process = &self.windows[window - 1];
+        !(process.launched || process.shell || (self.editing && self.active == window))
+    }
+
+    fn add_files(&mut self, editing: bool ) -> Result<(), Fault> {
+        let directory = self.files.list_directory().or_fault("listing files")?;
         let file_count = directory.0;
         let filenames = directory.1;
 
         if editing || file_count != self.file_count {
-            self.file_count = file_count;  
-            let col_width = WINDOW_WIDTH / 3;
-            let mut word_count = 0;
-            for i in FIRST_BORDER_ROW+1..WINDOW_HEIGHT {
-                let mut count = 1;
-                for j in 1..WINDOW_WIDTH{
-                    if count < col_width {
-                        if word_count < 1 {
-                            self.def_buffer[count - 1] = filenames[word_count][count - 1] as char;
-                            self.q1_buffer[count - 1] = filenames[word_count][count - 1] as char;
-                            self.q2_buffer[count - 1] = filenames[word_count][count - 1] as char;
-                            self.q3_buffer[count - 1] = filenames[word_count][count - 1] as char;
-                            self.q4_buffer[count - 1] = filenames[word_count][count - 1] as char;
-                        }
-                        self.screen[i][j] = filenames[word_count][count - 1] as char;
-                        self.screen[i][j + WINDOW_WIDTH + 2] = filenames[word_count][count - 1] as char;
-                        self.screen[i + WINDOW_HEIGHT+ 1][j] = filenames[word_count][count - 1] as char;
-                        self.screen[i + WINDOW_HEIGHT + 1][j + WINDOW_WIDTH + 2] = filenames[word_count][count - 1] as char;
-                        count += 1;
-                    } else if count == col_width{
-                        word_count += 1;
-                        count = 1;
-                    }
+            self.file_count = file_count;
+            self.measure_usage()?;
+            for (c, byte) in self.def_buffer.iter_mut().zip(filenames[0]) {
+                *c = byte as char;
+            }
+            for window in 1..=NUM_WINDOWS {
+                if !self.shows_files(window) {
+                    continue;
                 }
-                word_count += 1;
+                self.windows[window - 1].filename = self.def_buffer;
+                self.draw_file_list(window, file_count, &filenames);
+            }
         }
+        Ok(())
+    }
+
+    // Files per row of the browser: one per row when sizes are shown.
+    fn file_columns(&self) -> usize {
+        if self.show_sizes {1} else {FILE_COLUMNS}
+    }
+
+    // Lays one screenful of filenames into a window's grid, starting from the
+    // row the active window is scrolled to, with markers when there are more
+    // files above or below and a disk usage footer underneath.
+    fn draw_file_list(&mut self, window: usize, file_count: usize, filenames: &[[u8; MAX_FILENAME_BYTES]; MAX_FILES_STORED]) {
+        let columns = self.file_columns();
+        let first = if window == self.active {self.file_scroll * columns} else {0};
+        let top = window_rows(window).start;
+        let cols = window_cols(window);
+        let marker_col = cols.end - 1;
+        for row in top..=top + FILE_ROWS {
+            for col in cols.clone() {
+                self.screen[row][col] = ' ';
+            }
+        }
+        for slot in 0..FILE_ROWS * columns {
+            let f = first + slot;
+            let (row, col) = file_spot(window, slot, columns);
+            for (c, cell) in self.screen[row][col..col + MAX_FILENAME_BYTES].iter_mut().enumerate() {
+                *cell = if f < file_count {filenames[f][c] as char} else {'\0'};
+            }
+            if self.show_sizes && f < file_count {
+                let size = self.file_sizes[f];
+                let mut out = RowWriter::new(&mut self.screen[row][col + MAX_FILENAME_BYTES + 1..marker_col]);
+                let _ = write!(out, "{:>5} B {:>2} blocks", size, size.div_ceil(BLOCK_SIZE));
+            }
+        }
+        self.screen[top][marker_col] = if first > 0 {'^'} else {' '};
+        self.screen[top + FILE_ROWS - 1][marker_col] = if first + FILE_ROWS * columns < file_count {'v'} else {' '};
+
+        let mut footer = RowWriter::new(&mut self.screen[top + FILE_ROWS][cols.start..marker_col]);
+        let _ = write!(footer, "{}/{} blocks  {}/{} files", self.blocks_used, NUM_BLOCKS, file_count, MAX_FILES_STORED);
     }
-}
 
-    fn handle_raw(&mut self, key: KeyCode) {
+    fn handle_raw(&mut self, key: KeyCode) -> Result<(), Fault> {
+        if self.edit_input(key) {
+            return Ok(());
+        }
         match key {
             KeyCode::F1=> {
-                self.update_active(1)
+                self.update_active(1)?
             }
             KeyCode::F2=> {
-                self.update_active(2)
+                self.update_active(2)?
             }
             KeyCode::F3=> {
-                self.update_active(3)
+                self.update_active(3)?
             }
             KeyCode::F4=> {
-                self.update_active(4)
+                self.update_active(4)?
             }
             KeyCode::F5=> {
-                self.update_active(5);
-            } 
+                self.update_active(5)?;
+            }
             KeyCode::F6=> {
                 self.buffer_offset = 0;
+                self.file_scroll = 0;
                 if self.editing {
-                    let buffer = self.empty_screen();
-                    let name_buff = self.clear_editing();
-                    let filename = from_utf8(&name_buff).unwrap();
-                    // println!("{:?}", buffer);
-                    // panic!();
+                    let name_buff = self.editing_name();
+                    let filename = from_utf8(&name_buff).or_fault("naming the edited file")?;
+                    let mut buffer = [0; MAX_FILE_BYTES];
+                    let count = self.editor.save_into(&mut buffer);
+                    // Stays in the editor when the file system refuses the
+                    // text, so that the edit is not lost.
+                    if let Err(e) = self.save_file(filename, &buffer[0..count]) {
+                        serial_println!("[kernel] saving {} failed: {:?}", filename.trim_end_matches('\0'), e);
+                        self.show_notice(format_args!("Save failed: {:?} - edit kept", e));
+                        self.draw();
+                        return Ok(());
+                    }
+                    serial_println!("[kernel] saved {}", filename.trim_end_matches('\0'));
+                    self.empty_screen();
+                    self.clear_editing();
                     self.screen = update_screen(self.screen, self.active);
-                    self.add_files(true);
                     self.editing = false;
-                    let fd = self.files.open_create(filename).unwrap();
-                    self.files.write(fd, &buffer);
-                    self.files.close(fd);
-                } else if self.active == 1 {
-                    self.q1_run = (false, false, false);
-                    self.waiting_check();    
-                    self.input1 = ['\0' ; 20];
-                    self.input_flag1 = false;
-                    self.input_offset1 = 0;
-                    self.empty_screen();
-                    self.screen = update_screen(self.screen, 1);
-                    self.add_files(true); 
-                    self.ticks[0] = 0
-                } else if self.active == 2 {
-                    self.q2_run = (false, false, false);
-                    self.waiting_check();    
-                    self.input2 = ['\0' ; 20];
-                    self.input_flag2 = false;
-                    self.input_offset2 = 0;
-                    self.empty_screen();
-                    self.screen = update_screen(self.screen, 2);
-                    self.add_files(true); 
-                    self.ticks[1] = 0
-                }else if self.active == 3 {
-                    self.q3_run = (false, false, false);
-                    self.waiting_check();    
-                    self.input3 = ['\0' ; 20];
-                    self.input_flag3 = false;
-                    self.input_offset3 = 0;
-                    self.empty_screen();
-                    self.screen = update_screen(self.screen, 3);
-                    self.add_files(true); 
-                    self.ticks[2] = 0
-                }
-                else if self.active == 4 {
-                    self.q4_run = (false, false, false);
-                    self.waiting_check();    
-                    self.input1 = ['\0' ; 20];
-                    self.input_flag1 = false;
-                    self.input_offset1 = 0;
+                    self.sync_file(&name_buff)?;
+                    self.add_files(true)?;
+                } else if let Some(window) = self.active_window() {
+                    if self.windows[window - 1].running {
+                        serial_println!("[kernel] F{}: killed", window);
+                    }
+                    self.windows[window - 1].stop();
                     self.empty_screen();
-                    self.screen = update_screen(self.screen, 4);
-                    self.add_files(true); 
-                    self.ticks[3] = 0
+                    self.screen = update_screen(self.screen, window);
+                    self.add_files(true)?;
                 }
                 self.draw();
-            } 
+            }
+            KeyCode::F10 => self.detach()?,
+            KeyCode::F11 => self.select_next_job(),
+            KeyCode::F7 => self.change_priority(false),
+            KeyCode::F9 => self.toggle_pause(),
+            KeyCode::F8 => self.change_priority(true),
+            KeyCode::ArrowRight if self.editing => self.editor.right(),
+            KeyCode::ArrowLeft if self.editing => self.editor.left(),
+            KeyCode::ArrowDown if self.editing => self.editor.down(),
+            KeyCode::ArrowUp if self.editing => self.editor.up(),
+            KeyCode::Home if self.editing => self.editor.home(),
+            KeyCode::End if self.editing => self.editor.end(),
+            KeyCode::PageUp if self.editing => {
+                for _ in window_rows(self.active) {
+                    self.editor.up();
+                }
+            }
+            KeyCode::PageDown if self.editing => {
+                for _ in window_rows(self.active) {
+                    self.editor.down();
+                }
+            }
             KeyCode::ArrowRight => {
-                self.highlight('r');
+                self.highlight('r')?;
             }
             KeyCode::ArrowLeft => {
-                self.highlight('l');
+                self.highlight('l')?;
             }
             KeyCode::ArrowDown => {
-                self.highlight('d');
+                self.highlight('d')?;
             }
             KeyCode::ArrowUp => {
-                self.highlight('u');
+                self.highlight('u')?;
+            }
+            KeyCode::PageUp => {
+                if let Some(window) = self.active_window() {
+                    if self.shows_files(window) {
+                        self.page_files(false)?;
+                    } else {
+                        self.windows[window - 1].console.page_up(window_rows(window).len());
+                    }
+                }
+            }
+            KeyCode::PageDown => {
+                if let Some(window) = self.active_window() {
+                    if self.shows_files(window) {
+                        self.page_files(true)?;
+                    } else {
+                        self.windows[window - 1].console.page_down(window_rows(window).len());
+                    }
+                }
             }
-            
+
             _ => ()
             }
+            Ok(())
         }
-            
-    fn create_file(&mut self) {
 
-        let mut buffer = [0; MAX_FILENAME_BYTES];
-        let start = FILENAME_PROMPT.len();
-        let mut count = 0;
+    // The line being typed for the active window's program or shell, if
+    // there is one.
+    fn typed_input(&mut self) -> Option<&mut WindowInput> {
+        let window = self.active_window()?;
+        let process = &mut self.windows[window - 1];
+        if process.takes_input() {Some(&mut process.input)} else {None}
+    }
+
+    // Moves around the running program's or shell's input line, or through
+    // the lines entered before it with the up and down arrows. Returns whether `key`
+    // was used for that.
+    fn edit_input(&mut self, key: KeyCode) -> bool {
+        let input = match self.typed_input() {
+            Some(input) => input,
+            None => return false,
+        };
+        match key {
+            KeyCode::ArrowLeft => input.left(),
+            KeyCode::ArrowRight => input.right(),
+            KeyCode::Home => input.home(),
+            KeyCode::End => input.end(),
+            KeyCode::ArrowUp => input.older(),
+            KeyCode::ArrowDown => input.newer(),
+            _ => return false,
+        }
+        true
+    }
+
+    // Creates an empty file named by what was typed at F5, unless the name is
+    // blank or taken.
+    fn create_file(&mut self) -> Result<(), Fault> {
+        let buffer = self.take_prompt_field(FILENAME_PROMPT.len());
+        let name = buffer.trim_ascii_end();
+        if name.is_empty() {
+            return Ok(());
+        }
+        let filename = from_utf8(name).or_fault("naming the new file")?;
+        if self.file_exists(name)? {
+            self.show_notice(format_args!("{} already exists", filename));
+            return Ok(());
+        }
+        let fd = self.files.open_create(filename).or_fault("creating a file")?;
+        self.files.close(fd).or_fault("creating a file")?;
+        serial_println!("[kernel] created {}", filename);
+        self.sync_file(name)
+    }
+
+    // Adds a character to the filename typed on the top row at `start`.
+    fn type_into_prompt(&mut self, start: usize, key: char) {
         for i in start..start+MAX_FILENAME_BYTES {
+            if self.screen[0][i] == ' '{
+                self.screen[0][i] = key;
+                break;
+            }
+        }
+    }
+
+    fn backspace_prompt(&mut self, start: usize) {
+        // Leaves the prompt text alone when the field is empty.
+        if let Some(last) = (start..start+MAX_FILENAME_BYTES).rev().find(|i| self.screen[0][*i] != ' ') {
+            self.screen[0][last] = ' ';
+        }
+    }
+
+    // Removes the filename typed on the top row at `start` and returns it.
+    fn take_prompt_field(&mut self, start: usize) -> [u8; MAX_FILENAME_BYTES] {
+        let mut buffer = [0; MAX_FILENAME_BYTES];
+        for (count, i) in (start..start+MAX_FILENAME_BYTES).enumerate() {
             buffer[count] = self.screen[0][i] as u8;
             self.screen[0][i] = ' ';
-            count += 1;
         }
-        let filename = from_utf8(&buffer).unwrap();
-        let fd = self.files.open_create(filename).unwrap();
-        self.files.close(fd).unwrap();
+        buffer
     }
 
-    fn empty_screen(&mut self) -> [u8; 450]  {
-        let mut buffer: [u8; 450] = [0; 450];
-        let mut count = 0;
-        if self.active == 1 {
-            for i in FIRST_BORDER_ROW + 1..MID_HEIGHT {
-                for j in 1..MID_WIDTH {
-                    buffer[count] = self.screen[i][j] as u8;
-                    self.screen[i][j] = ' ';
-                    count += 1;
-                }
-            }
-        } else if self.active == 2 {
-            for i in FIRST_BORDER_ROW + 1..MID_HEIGHT {
-                for j in MID_WIDTH + 1..WINDOWS_WIDTH {
-                    buffer[count] = self.screen[i][j] as u8;
-                    self.screen[i][j] = ' ';
-                    count += 1;
-                }
-            }
-        } else if self.active == 3 {
-            for i in MID_HEIGHT + 1..LAST_BORDER_ROW {
-                for j in 1..MID_WIDTH {
-                    buffer[count] = self.screen[i][j] as u8;
-                    self.screen[i][j] = ' ';
-                    count += 1;
-                }
-            }
-        } else if self.active == 4 {
-            for i in MID_HEIGHT + 1..LAST_BORDER_ROW {
-                for j in MID_WIDTH + 1..WINDOWS_WIDTH {
-                    buffer[count] = self.screen[i][j] as u8;
-                    self.screen[i][j] = ' ';
-                    count += 1;
-                }
-            }
-        
+    // Asks on the top row whether to delete or what to rename the file
+    // highlighted in the active window.
+    fn start_file_prompt(&mut self, kind: PromptKind) {
+        let process = &self.windows[self.active - 1];
+        let len = process.filename_len().min(MAX_FILENAME_BYTES);
+        if len == 0 {
+            return;
         }
-        return buffer;
+        let (before, after) = match kind {
+            PromptKind::Delete => ("Delete ", "? (y/n)"),
+            PromptKind::Rename => ("Rename ", " to: "),
+            PromptKind::Attach => return,
+        };
+        let mut row = [' '; WINDOWS_WIDTH];
+        let mut col = 0;
+        for c in before.chars().chain(process.filename[0..len].iter().copied()).chain(after.chars()) {
+            row[col] = c;
+            col += 1;
+        }
+        let target = process.filename_bytes();
+        self.cover_top_row(&row);
+        self.file_prompt = Some(FilePrompt {kind, target, start: col, window: self.active});
     }
 
-    fn clear_editing(&mut self) -> [u8; MAX_FILENAME_BYTES] {
-        let mut name = [0; MAX_FILENAME_BYTES];
-        if self.active == 1 {
-            self.screen[FIRST_BORDER_ROW][2] = '*';
-            self.screen[FIRST_BORDER_ROW][3] = '*';
-            self.screen[FIRST_BORDER_ROW][4] = '*';
-            self.screen[FIRST_BORDER_ROW][5] = '*';
-            let mut len = 0;
-            
-            for i in self.q1_buffer {
-                if i != '\0'{
-                    if len == MAX_FILENAME_BYTES {
-
-                    } else{
-                        name[len] = i as u8;
+    // Asks on the top row which background job to show in `window`, if there
+    // are any.
+    fn start_attach_prompt(&mut self, window: usize) {
+        if !self.jobs.iter().any(|job| job.launched) {
+            return;
+        }
+        let prompt = "Attach job #";
+        let mut row = [' '; WINDOWS_WIDTH];
+        for (i, c) in prompt.chars().enumerate() {
+            row[i] = c;
+        }
+        self.cover_top_row(&row);
+        self.file_prompt = Some(FilePrompt {kind: PromptKind::Attach, target: [0; MAX_FILENAME_BYTES], start: prompt.len(), window});
+    }
+
+    fn answer_file_prompt(&mut self, prompt: FilePrompt, key: char) -> Result<(), Fault> {
+        match prompt.kind {
+            PromptKind::Delete => {
+                if key == 'y' {
+                    self.rewrite_files(trim_filename(&prompt.target), None)?;
+                }
+                self.close_file_prompt()?;
+            }
+            PromptKind::Rename => {
+                if key.is_alphanumeric() {
+                    self.type_into_prompt(prompt.start, key);
+                } else if key == '\u{08}' {
+                    self.backspace_prompt(prompt.start);
+                } else if key == '\n' {
+                    let name = self.take_prompt_field(prompt.start);
+                    let len = name.iter().position(|c| *c == b' ').unwrap_or(MAX_FILENAME_BYTES);
+                    if len > 0 && !self.file_exists(&name[0..len])? {
+                        self.rewrite_files(trim_filename(&prompt.target), Some(&name[0..len]))?;
                     }
-                    len += 1
-                } else {
-                    break;
+                    self.close_file_prompt()?;
+                } else if key == '\u{1b}' {
+                    self.close_file_prompt()?;
                 }
             }
-            for i in 0..len{
-                self.screen[FIRST_BORDER_ROW][6 + i] = '*';
-                plot('*', 6 + i, FIRST_BORDER_ROW , ColorCode::new(Color::Black, Color::White));
-            }
-            
-        } else if self.active == 2 {
-            self.screen[FIRST_BORDER_ROW][MID_WIDTH + 2] = '*';
-            self.screen[FIRST_BORDER_ROW][MID_WIDTH + 3] = '*';
-            self.screen[FIRST_BORDER_ROW][MID_WIDTH + 4] = '*';
-            self.screen[FIRST_BORDER_ROW][MID_WIDTH + 5] = '*';
-            let mut len = 0;
-            for i in self.q2_buffer {
-                if i != '\0'{
-                    name[len] = i as u8;
-                    len += 1
-                } else {
-                    break;
+            PromptKind::Attach => {
+                if key.is_ascii_digit() {
+                    self.type_into_prompt(prompt.start, key);
+                } else if key == '\u{08}' {
+                    self.backspace_prompt(prompt.start);
+                } else if key == '\n' {
+                    let field = self.take_prompt_field(prompt.start);
+                    let pid = from_utf8(&field).ok().and_then(|field| field.trim().parse::<usize>().ok());
+                    let job = self.jobs.iter().position(|job| job.launched && Some(job.pid) == pid);
+                    self.close_file_prompt()?;
+                    // The window may have started a program or a shell
+                    // since the prompt opened, which attaching would end.
+                    if let Some(job) = job {
+                        if self.windows[prompt.window - 1].takes_input() {
+                            self.show_notice(format_args!("F{} is busy", prompt.window));
+                        } else {
+                            self.attach(prompt.window, job)?;
+                        }
+                    }
+                } else if key == '\u{1b}' {
+                    self.close_file_prompt()?;
                 }
             }
-            for i in 0..len{
-                self.screen[FIRST_BORDER_ROW][MID_WIDTH + 6 + i] = '*';
-                plot('*', MID_WIDTH + 6 + i, FIRST_BORDER_ROW , ColorCode::new(Color::Black, Color::White));
-            }
-        } else if self.active == 3 {
-            self.screen[MID_HEIGHT][2] = '*';
-            self.screen[MID_HEIGHT][3] = '*';
-            self.screen[MID_HEIGHT][4] = '*';
-            self.screen[MID_HEIGHT][5] = '*';
-            let mut len = 0;
-            for i in self.q3_buffer {
-                if i != '\0'{
-                    name[len] = i as u8;
-                    len += 1
-                } else {
-                    break;
-                }
+        }
+        Ok(())
+    }
+
+    fn close_file_prompt(&mut self) -> Result<(), Fault> {
+        self.file_prompt = None;
+        self.uncover_top_row();
+        self.files_changed()
+    }
+
+    // Redraws the file grids from the top after files were added, removed
+    // or renamed.
+    fn files_changed(&mut self) -> Result<(), Fault> {
+        self.buffer_offset = 0;
+        self.file_scroll = 0;
+        self.add_files(true)
+    }
+
+    fn file_exists(&mut self, name: &[u8]) -> Result<bool, Fault> {
+        let (count, filenames) = self.files.list_directory().or_fault("listing files")?;
+        // Names typed at F5 used to be stored padded with spaces.
+        Ok(filenames[0..count].iter().any(|f| trim_filename(f).trim_ascii_end() == name.trim_ascii_end()))
+    }
+
+    // The filesystem crate cannot remove a directory entry, so deleting or
+    // renaming a file copies every other file onto a freshly formatted disk.
+    // `target` is dropped, or stored as `new_name` when one is given. Only
+    // the files from `target` on change places, so only they are written
+    // back to the persistent disk.
+    fn rewrite_files(&mut self, target: &[u8], new_name: Option<&[u8]>) -> Result<(), Fault> {
+        let (count, filenames) = self.files.list_directory().or_fault("listing files")?;
+        let first = filenames[0..count].iter().position(|f| trim_filename(f) == target).unwrap_or(count);
+        let mut fresh: KernelFileSystem = filesystem::FileSystem::new(RamDisk::new());
+        let mut contents = [0; MAX_FILE_BYTES];
+        for filename in filenames[0..count].iter() {
+            let name = trim_filename(filename);
+            let dest = if name != target {
+                name
+            } else if let Some(new_name) = new_name {
+                new_name
+            } else {
+                continue;
+            };
+            let len = self.read_file(from_utf8(name).or_fault("listing files")?, &mut contents)?;
+            let fd = fresh.open_create(from_utf8(dest).or_fault("naming a file")?).or_fault("copying a file")?;
+            if len > 0 {
+                fresh.write(fd, &contents[0..len]).or_fault("copying a file")?;
+            }
+            fresh.close(fd).or_fault("copying a file")?;
+        }
+        self.files = fresh;
+        let target = from_utf8(target).or_fault("naming a file")?;
+        match new_name {
+            Some(new_name) => serial_println!("[kernel] renamed {} to {}", target, from_utf8(new_name).or_fault("naming a file")?),
+            None => serial_println!("[kernel] deleted {}", target),
+        }
+        let changed = if new_name.is_some() {first..first + 1} else {first..count};
+        self.sync_disk(changed)
+    }
+
+    fn empty_screen(&mut self) {
+        if let Some(window) = self.active_window() {
+            self.clear_window(window);
+        }
+    }
+
+    fn clear_window(&mut self, window: usize) {
+        for i in window_rows(window) {
+            for j in window_cols(window) {
+                self.screen[i][j] = ' ';
             }
-            for i in 0..len{
-                self.screen[MID_HEIGHT][6 + i] = '*';
-                plot('*', 6 + i, MID_HEIGHT , ColorCode::new(Color::Black, Color::White));
-            }
-        } else if self.active == 4 {
-            self.screen[MID_HEIGHT][MID_WIDTH + 2] = '*';
-            self.screen[MID_HEIGHT][MID_WIDTH + 3] = '*';
-            self.screen[MID_HEIGHT][MID_WIDTH + 4] = '*';
-            self.screen[MID_HEIGHT][MID_WIDTH + 5] = '*';
-            let mut len = 0;
-            for i in self.q4_buffer {
-                if i != '\0'{
-                    name[len] = i as u8;
-                    len += 1
-                } else {
-                    break;
-                }
+        }
+    }
+
+    fn clear_editing(&mut self) {
+        if let Some(window) = self.active_window() {
+            let row = header_row(window);
+            let col = border_col(window);
+            for i in 2..6 {
+                self.screen[row][col + i] = '*';
             }
-            for i in 0..len{
-                self.screen[MID_HEIGHT][MID_WIDTH + 6 + i] = '*';
-                plot('*', MID_WIDTH + 6 + i, MID_HEIGHT , ColorCode::new(Color::Black, Color::White));
+            let len = self.windows[window - 1].filename_len().min(MAX_FILENAME_BYTES);
+            for i in 0..len {
+                self.screen[row][col + 6 + i] = '*';
+                self.display.plot('*', col + 6 + i, row, ColorCode::new(Color::Black, Color::White));
             }
         }
-        return name;
     }
-    
-    fn setup_editing_window(&mut self) {
-        self.editing = true;
-        if self.active == 1 {
-            self.screen[FIRST_BORDER_ROW][2] = '(';
-            self.screen[FIRST_BORDER_ROW][3] = 'F';
-            self.screen[FIRST_BORDER_ROW][4] = '6';
-            self.screen[FIRST_BORDER_ROW][5] = ')';
-            let mut len = 0;
-            for i in self.q1_buffer {
-                if i != '\0'{
-                    len += 1
-                } else {
-                    break;
-                }
+
+    // Name of the file being edited in the active window.
+    fn editing_name(&self) -> [u8; MAX_FILENAME_BYTES] {
+        let mut name = [0; MAX_FILENAME_BYTES];
+        if let Some(window) = self.active_window() {
+            let process = &self.windows[window - 1];
+            let len = process.filename_len().min(MAX_FILENAME_BYTES);
+            for (byte, c) in name.iter_mut().zip(&process.filename[0..len]) {
+                *byte = *c as u8;
             }
-            for i in 0..len{
-                self.screen[FIRST_BORDER_ROW][6 + i] = self.q1_buffer[i];
-                plot(self.q1_buffer[i], 6 + i, FIRST_BORDER_ROW , ColorCode::new(Color::Black, Color::White));
-            }
-            
-        }else if self.active == 2 {
-            self.screen[FIRST_BORDER_ROW][MID_WIDTH + 2] = '(';
-            self.screen[FIRST_BORDER_ROW][MID_WIDTH + 3] = 'F';
-            self.screen[FIRST_BORDER_ROW][MID_WIDTH + 4] = '6';
-            self.screen[FIRST_BORDER_ROW][MID_WIDTH + 5] = ')';
-            let mut len = 0;
-            for i in self.q2_buffer {
-                if i != '\0'{
-                    len += 1
-                } else {
-                    break;
-                }
-            }
-            for i in 0..len{
-                self.screen[FIRST_BORDER_ROW][MID_WIDTH + 6 + i] = self.q2_buffer[i];
-                plot(self.q2_buffer[i], MID_WIDTH + 6 + i, FIRST_BORDER_ROW , ColorCode::new(Color::Black, Color::White));
-            } 
-        } else if self.active == 3 {
-            self.screen[MID_HEIGHT][2] = '(';
-            self.screen[MID_HEIGHT][3] = 'F';
-            self.screen[MID_HEIGHT][4] = '6';
-            self.screen[MID_HEIGHT][5] = ')';
-            let mut len = 0;
-            for i in self.q3_buffer {
-                if i != '\0'{
-                    len += 1
-                } else {
-                    break;
-                }
-            }
-            for i in 0..len{
-                self.screen[MID_HEIGHT][6 + i] = self.q3_buffer[i];
-                plot(self.q3_buffer[i], 6 + i, MID_HEIGHT , ColorCode::new(Color::Black, Color::White));
-            } 
-        } else if self.active == 4 {
-            self.screen[MID_HEIGHT][MID_WIDTH + 2] = '(';
-            self.screen[MID_HEIGHT][MID_WIDTH + 3] = 'F';
-            self.screen[MID_HEIGHT][MID_WIDTH + 4] = '6';
-            self.screen[MID_HEIGHT][MID_WIDTH + 5] = ')';
-            let mut len = 0;
-            for i in self.q4_buffer {
-                if i != '\0'{
-                    len += 1
-                } else {
-                    break;
-                }
-            }
-            for i in 0..len{
-                self.screen[MID_HEIGHT][MID_WIDTH + 6 + i] = self.q4_buffer[i];
-                plot(self.q4_buffer[i], MID_WIDTH + 6 + i, MID_HEIGHT , ColorCode::new(Color::Black, Color::White));
-            } 
         }
-    } 
+        name
+    }
 
-    fn read_file_to_window(&mut self) {
-        let mut buffer = [0; MAX_FILENAME_BYTES];
-        if self.active == 1 {
-            for (i,c) in self.q1_buffer.iter().enumerate() {
-                if i == MAX_FILENAME_BYTES {
-                    break;
+    fn save_file(&mut self, filename: &str, contents: &[u8]) -> FileSystemResult<()> {
+        let fd = self.files.open_create(filename)?;
+        let written = self.files.write(fd, contents);
+        self.files.close(fd)?;
+        written
+    }
+
+    // Writes a message over the top row until the next key.
+    fn show_notice(&mut self, message: core::fmt::Arguments) {
+        let mut row = [' '; WINDOWS_WIDTH];
+        let _ = RowWriter::new(&mut row).write_fmt(message);
+        self.cover_top_row(&row);
+        self.notice = true;
+    }
+
+    // Writes `row` over the top row, keeping what was there, like a filename
+    // half typed at F5, for uncover_top_row to put back.
+    fn cover_top_row(&mut self, row: &[char; WINDOWS_WIDTH]) {
+        if self.file_prompt.is_none() && !self.notice {
+            self.file_entry[0..WINDOWS_WIDTH].copy_from_slice(&self.screen[0][0..WINDOWS_WIDTH]);
+        }
+        self.screen[0][0..WINDOWS_WIDTH].copy_from_slice(row);
+    }
+
+    fn uncover_top_row(&mut self) {
+        self.screen[0][0..WINDOWS_WIDTH].copy_from_slice(&self.file_entry[0..WINDOWS_WIDTH]);
+    }
+
+    // Measures each file's size and the blocks in use again, if the files
+    // changed since they were last measured.
+    fn measure_usage(&mut self) -> Result<(), Fault> {
+        if !self.usage_stale {
+            return Ok(());
+        }
+        let (count, filenames) = self.files.list_directory().or_fault("listing files")?;
+        let mut contents = [0; MAX_FILE_BYTES];
+        for (f, filename) in filenames[0..count].iter().enumerate() {
+            let name = trim_filename(filename);
+            self.file_sizes[f] = self.read_file(from_utf8(name).or_fault("listing files")?, &mut contents)?;
+        }
+        self.blocks_used = NUM_BLOCKS - self.measure_free_blocks()?;
+        self.usage_stale = false;
+        Ok(())
+    }
+
+    // Free blocks as the file system itself counts them, metadata and all.
+    // The filesystem crate cannot report this, so a scratch copy of the
+    // files is grown block by block until the disk is full.
+    fn measure_free_blocks(&mut self) -> Result<usize, Fault> {
+        let (count, filenames) = self.files.list_directory().or_fault("listing files")?;
+        let mut scratch: KernelFileSystem = filesystem::FileSystem::new(RamDisk::new());
+        let mut contents = [0; MAX_FILE_BYTES];
+        let mut sizes = [0; MAX_FILES_STORED];
+        for f in 0..count {
+            let name = from_utf8(trim_filename(&filenames[f])).or_fault("listing files")?;
+            sizes[f] = self.read_file(name, &mut contents)?;
+            let fd = scratch.open_create(name).or_fault("measuring free space")?;
+            if sizes[f] > 0 {
+                scratch.write(fd, &contents[0..sizes[f]]).or_fault("measuring free space")?;
+            }
+            scratch.close(fd).or_fault("measuring free space")?;
+        }
+        let filler = [0; BLOCK_SIZE];
+        let mut free = 0;
+        // Once the disk is full every later file stops at its first block.
+        for f in 0..MAX_FILES_STORED {
+            let fd = if f < count {
+                scratch.open_append(from_utf8(trim_filename(&filenames[f])).or_fault("listing files")?)
+            } else {
+                let name = [b'~', b'0' + (f / 10) as u8, b'0' + (f % 10) as u8];
+                if filenames[0..count].iter().any(|f| trim_filename(f) == name) {
+                    continue;
                 }
-                buffer[i] = *c as u8;
-            }
-        } else if self.active == 2 {
-            for (i,c) in self.q2_buffer.iter().enumerate() {
-                if i == MAX_FILENAME_BYTES {
-                    break;
+                scratch.open_create(from_utf8(&name).or_fault("measuring free space")?)
+            };
+            let fd = match fd {
+                Ok(fd) => fd,
+                Err(_) => break,
+            };
+            // Filling the last block first takes no new block.
+            let mut result = scratch.write(fd, &filler[0..(BLOCK_SIZE - sizes[f] % BLOCK_SIZE) % BLOCK_SIZE]);
+            while result.is_ok() {
+                result = scratch.write(fd, &filler);
+                if result.is_ok() {
+                    free += 1;
                 }
-                buffer[i] = *c as u8;
             }
-        } else if self.active == 3 {
-            for (i,c) in self.q3_buffer.iter().enumerate() {
-                if i == MAX_FILENAME_BYTES {
-                    break;
-                }
-                buffer[i] = *c as u8;
+            scratch.close(fd).or_fault("measuring free space")?;
+        }
+        Ok(free)
+    }
+
+    fn setup_editing_window(&mut self) {
+        self.editing = true;
+        if let Some(window) = self.active_window() {
+            let row = header_row(window);
+            let col = border_col(window);
+            for (i, c) in "(F6)".chars().enumerate() {
+                self.screen[row][col + 2 + i] = c;
             }
-        } else if self.active == 4 {
-            for (i,c) in self.q4_buffer.iter().enumerate() {
-                if i == MAX_FILENAME_BYTES {
-                    break;
-                }
-                buffer[i] = *c as u8;
+            let process = &self.windows[window - 1];
+            for i in 0..process.filename_len() {
+                self.screen[row][col + 6 + i] = process.filename[i];
+                self.display.plot(process.filename[i], col + 6 + i, row, ColorCode::new(Color::Black, Color::White));
             }
         }
-        
-        let filename = from_utf8(&buffer).unwrap();
-        let fd = self.files.open_read(filename).unwrap();
+    }
+
+    // Reads a whole file into `file`, returning the number of bytes read.
+    fn read_file(&mut self, filename: &str, file: &mut [u8; MAX_FILE_BYTES]) -> Result<usize, Fault> {
+        let fd = self.files.open_read(filename).or_fault("opening a file")?;
         let mut count = 0;
-        let mut file = ['\0' ; 10000];
         let mut buffer = [0;10];
-        
-        loop{
-            let num_bytes = self.files.read(fd, &mut buffer).unwrap();
-            let s = core::str::from_utf8(&buffer[0..num_bytes]).unwrap();
-            for c in s.chars() {
-                file[count] = c;
-                
-                count += 1;
-            }
-            if num_bytes < buffer.len() {
-                self.files.close(fd);
-                break;
+
+        // Closes the file however the reading ends, so that a failed read
+        // does not keep it open.
+        let read = loop{
+            match self.files.read(fd, &mut buffer) {
+                Ok(num_bytes) => {
+                    file[count..count + num_bytes].copy_from_slice(&buffer[0..num_bytes]);
+                    count += num_bytes;
+                    if num_bytes < buffer.len() {
+                        break Ok(count);
+                    }
+                }
+                Err(e) => break Err(e),
             }
+        };
+        self.files.close(fd).and(read).or_fault("reading a file")
+    }
+
+    fn read_file_to_window(&mut self) -> Result<(), Fault> {
+        let window = match self.active_window() {
+            Some(window) => window,
+            None => return Ok(()),
+        };
+        if self.windows[window - 1].filename_len() == 0 {
+            return Ok(());
         }
+        let buffer = self.windows[window - 1].filename_bytes();
+        let filename = from_utf8(&buffer).or_fault("naming the file to edit")?;
+        let mut file = [0; MAX_FILE_BYTES];
+        let count = self.read_file(filename, &mut file)?;
+        self.editor.load(&file[0..count]);
 
         self.empty_screen();
         self.setup_editing_window();
-        let mut offset_row = 0;
-        let mut offset_col = 0;
-        let mut col_reset = 0;
-        if self.active == 1 {
-            offset_row = 2;
-            offset_col = 1;
-            col_reset = 1;
-        } else if self.active == 2 {
-            offset_row = 2;
-            offset_col = MID_WIDTH + 1;
-            col_reset = MID_WIDTH + 1;
-        } else if self.active == 3 {
-            offset_row = MID_HEIGHT + 1;
-            offset_col = 1;
-            col_reset = 1;
-        } else if self.active == 4 {
-            offset_row = MID_HEIGHT + 1;
-            offset_col = MID_WIDTH + 1;
-            col_reset = MID_WIDTH + 1;
-        }
-
-        for (i, c) in file.iter().enumerate() {
-            if i == count {
-                break;
-            }
-            if offset_row % MID_HEIGHT == 0{
-                break;
-            }
-            if offset_col % MID_WIDTH == 0 {
-                offset_row += 1;
-                offset_col = col_reset;
-            }
-            if *c == '\n' {
-                offset_row += 1;
-                offset_col = col_reset;
-            } else {
-                self.screen[offset_row][offset_col] = *c;
-                offset_col += 1;
-            }
+        Ok(())
+    }
+
+    fn edit(&mut self, key : char) {
+        match key {
+            '\u{08}' => self.editor.backspace(),
+            '\u{7f}' => self.editor.delete(),
+            _ => self.editor.insert(key as u8),
         }
     }
 
-    fn edit(&mut self, key : char, active: usize) {
-        let mut last_char = false;
-        let mut spot = (0,0);
-        if active == 1 {
-            for i in FIRST_BORDER_ROW + 1 .. MID_HEIGHT {
-                for j in 1..MID_WIDTH{
-                    if self.screen[i][j] == ' ' && !last_char {
-                        spot = (i, j); 
-                        last_char = true;
-                    }
-                    if self.screen[i][j] != ' ' && last_char {
-                        last_char = false;
-                    }
-                }
+    // Copies the editor's text into the window it is being edited in.
+    fn draw_editor(&mut self, window: usize) {
+        let rows = window_rows(window);
+        let cols = window_cols(window);
+        for i in rows.clone() {
+            for j in cols.clone() {
+                self.screen[i][j] = ' ';
             }
-        } else if active == 2 {
-            for i in FIRST_BORDER_ROW + 1 .. MID_HEIGHT {
-                for j in MID_WIDTH + 1..WINDOWS_WIDTH{
-                    if self.screen[i][j] == ' ' && !last_char {
-                        spot = (i, j); 
-                        last_char = true;
-                    }
-                    if self.screen[i][j] != ' ' && last_char {
-                        last_char = false;
-                    }
-                }
-            }
-        } else if active == 3 {
-            for i in MID_HEIGHT + 1 .. LAST_BORDER_ROW {
-                for j in 1..MID_WIDTH{
-                    if self.screen[i][j] == ' ' && !last_char {
-                        spot = (i, j); 
-                        last_char = true;
-                    }
-                    if self.screen[i][j] != ' ' && last_char {
-                        last_char = false;
-                    }
-                }
-            }
-        } else if active == 4 {
-            for i in MID_HEIGHT + 1 .. LAST_BORDER_ROW {
-                for j in MID_WIDTH + 1..WINDOWS_WIDTH{
-                    if self.screen[i][j] == ' ' && !last_char {
-                        spot = (i, j); 
-                        last_char = true;
-                    }
-                    if self.screen[i][j] != ' ' && last_char {
-                        last_char = false;
-                    }
-                }
-            }
-        }     
-
-        if self.new_line1 && active == 1{
-            self.new_line1 = false;
-            spot.0 = spot.0 + 1;
-            spot.1 = 1;
-        } else if self.new_line2 && active == 2 {
-            self.new_line2 = false;
-            spot.0 = spot.0 + 1;
-            spot.1 = MID_WIDTH + 1;
-        } else if self.new_line3 && active == 3{
-            self.new_line3 = false;
-            spot.0 = spot.0 + 1;
-            spot.1 = 1;
-        }
-            // if self.new_line {
-            //     self.new_line = false;
-            //     spot.0 = spot.0 + 1;
-            //     if self.active == 1 || self.active == 3 {
-            //         spot.1 = 1;
-            //     } else {
-            //         spot.1 = MID_WIDTH + 1;
-            //     }
-               
-            // }
-            
-            
-            
-
-            if key == '\u{08}' {
-                if spot.1 <= 1 {
-                    self.screen[spot.0 - 1][MID_WIDTH - 1] = ' ';
-                } else  {
-                    self.screen[spot.0][spot.1 - 1] = ' ';
-                }          
-            } else if key == '\n' {
-                if spot.0 + 1 == MID_HEIGHT {
-                    //Do something with scrolling?
-                } else  {
-                    self.screen[spot.0 + 1][1] = ' ';
-                    self.new_line = true;
-                    if active == 1 {
-                        self.new_line1 = true;
-                    } else if active == 2{
-                        self.new_line2 = true;
-                    } else if active == 3{
-                        self.new_line3 = true;
-                    }
-                }
-            }
-            else {
-                self.screen[spot.0][spot.1] = key;
+        }
+        self.editor.scroll_to_cursor(rows.len(), cols.len());
+        let (top, left) = self.editor.viewport();
+        for (r, line) in self.editor.lines().skip(top).take(rows.len()).enumerate() {
+            for (c, b) in line.iter().skip(left).take(cols.len()).enumerate() {
+                self.screen[rows.start + r][cols.start + c] = *b as char;
             }
-            
-            
+        }
     }
 
-    fn run(&mut self) {
-        let mut buffer = [0; MAX_FILENAME_BYTES];
-        if self.active == 1 {   
-            if !self.q1_run.0 {
-                if !self.q1_run.1 {
-                    self.empty_screen();
-                }
-                for i in 0..MAX_FILENAME_BYTES {
-                    buffer[i] = self.q1_buffer[i] as u8;
-                }
-            }  
-        } else if self.active == 2 {
-            if !self.q2_run.0 {
-                if !self.q2_run.1 {
-                    self.empty_screen();
-                }
-                for i in 0..MAX_FILENAME_BYTES {
-                    buffer[i] = self.q2_buffer[i] as u8;
-                }
-            }
-        } else if self.active == 3{
-            if !self.q3_run.0 {
-                if !self.q3_run.1 {
-                    self.empty_screen();
-                }
-                for i in 0..MAX_FILENAME_BYTES {
-                    buffer[i] = self.q3_buffer[i] as u8;
-                }
+    fn draw_editor_cursor(&mut self, window: usize) {
+        let rows = window_rows(window);
+        let cols = window_cols(window);
+        let (row, col) = self.editor.cursor_position();
+        let (top, left) = self.editor.viewport();
+        if row >= top && row - top < rows.len() && col >= left && col - left < cols.len() {
+            let (row, col) = (rows.start + row - top, cols.start + col - left);
+            self.display.plot(self.screen[row][col], col, row, highlight_color());
+        }
+    }
+
+    fn run(&mut self) -> Result<(), Fault> {
+        let window = match self.active_window() {
+            Some(window) => window,
+            None => return Ok(()),
+        };
+        if self.windows[window - 1].running || self.windows[window - 1].filename_len() == 0 {
+            return Ok(());
+        }
+        self.empty_screen();
+        let filename = self.windows[window - 1].filename;
+        self.launch(window - 1, filename)
+    }
+
+    // Runs the file highlighted in the active window as a background job,
+    // leaving the window showing the files.
+    fn run_in_background(&mut self) -> Result<(), Fault> {
+        let window = match self.active_window() {
+            Some(window) => window,
+            None => return Ok(()),
+        };
+        if self.windows[window - 1].filename_len() == 0 {
+            return Ok(());
+        }
+        match self.jobs.iter().position(|job| !job.launched) {
+            Some(job) => {
+                let filename = self.windows[window - 1].filename;
+                self.launch(NUM_WINDOWS + job, filename)
             }
-        } else if self.active == 4{
-            if !self.q4_run.0 {
-                if !self.waiting {
-                    self.empty_screen();
-                }
-                for i in 0..MAX_FILENAME_BYTES {
-                    buffer[i] = self.q4_buffer[i] as u8;
-                }
+            None => {
+                serial_println!("[kernel] no room for another background job");
+                Ok(())
             }
         }
-            
-        let filename = from_utf8(&buffer).unwrap();
-        let fd = self.files.open_read(filename).unwrap();
-        let mut count = 0;
-        let mut file = [0 ; 10000];
-        let mut buffer = [0;10];
-        
-        loop{
-            let num_bytes = self.files.read(fd, &mut buffer).unwrap();
-            let s = core::str::from_utf8(&buffer[0..num_bytes]).unwrap();
-            for c in s.chars() {
-                file[count] = c as u8;
-                
-                count += 1;
-            }
-            if num_bytes < buffer.len() {
-                self.files.close(fd);
-                break;
-            }
+    }
+
+    // Starts the program in `filename` as the process in `slot`, under a new
+    // process ID.
+    fn launch(&mut self, slot: usize, filename: [char; MAX_FILENAME_BYTES + 1]) -> Result<(), Fault> {
+        self.process_mut(slot).filename = filename;
+        let buffer = self.process(slot).filename_bytes();
+        let filename = from_utf8(&buffer).or_fault("naming the program to run")?;
+        let mut file = [0; MAX_FILE_BYTES];
+        let count = self.read_file(filename, &mut file)?;
+        let program = core::str::from_utf8(&file[0..count]).or_fault("reading the program to run")?;
+
+        let pid = self.next_pid;
+        self.next_pid += 1;
+        let process = self.process_mut(slot);
+        process.pid = pid;
+        process.interp = Interpreter::new(program);
+        process.input.clear();
+        process.queue.clear();
+        process.console = Console::new();
+        process.running = true;
+        process.waiting = false;
+        process.launched = true;
+        process.paused = false;
+        process.failed = false;
+        process.ticks = 0;
+        serial_println!("[kernel] {}: running {}", self.place(slot), filename.trim_end_matches('\0'));
+        Ok(())
+    }
+
+    // The scheduler numbers the windows' processes first, then the jobs'.
+    fn process(&self, slot: usize) -> &Process {
+        if slot < NUM_WINDOWS {&self.windows[slot]} else {&self.jobs[slot - NUM_WINDOWS]}
+    }
+
+    fn process_mut(&mut self, slot: usize) -> &mut Process {
+        if slot < NUM_WINDOWS {&mut self.windows[slot]} else {&mut self.jobs[slot - NUM_WINDOWS]}
+    }
+
+    // A process along with the screen its output goes to.
+    fn process_and_screen(&mut self, slot: usize) -> (&mut Process, &mut [[char; BUFFER_WIDTH]; BUFFER_HEIGHT]) {
+        let process = if slot < NUM_WINDOWS {&mut self.windows[slot]} else {&mut self.jobs[slot - NUM_WINDOWS]};
+        (process, &mut self.screen)
+    }
+
+    fn place(&self, slot: usize) -> Place {
+        if slot < NUM_WINDOWS {Place::Window(slot + 1)} else {Place::Job(self.jobs[slot - NUM_WINDOWS].pid)}
+    }
+
+    // Swaps the program in `window` with background job `job`, along with
+    // their priorities.
+    fn swap_with_job(&mut self, window: usize, job: usize) {
+        core::mem::swap(&mut self.windows[window - 1], &mut self.jobs[job]);
+        self.scheduler.swap(window - 1, NUM_WINDOWS + job);
+    }
+
+    // Moves background job `job` into `window`, closing any finished program
+    // there, and shows the job's output so far again from its log.
+    fn attach(&mut self, window: usize, job: usize) -> Result<(), Fault> {
+        self.windows[window - 1].stop();
+        self.swap_with_job(window, job);
+        self.empty_screen();
+        let process = &mut self.windows[window - 1];
+        let log = process.console.log;
+        process.console = Console::new();
+        KernelOutput::new(Place::Window(window), &mut self.screen, &mut process.console).replay(&log);
+        serial_println!("[kernel] #{}: attached to F{}", process.pid, window);
+        Ok(())
+    }
+
+    // Sends the program running in the active window to the background,
+    // leaving the window showing the files.
+    fn detach(&mut self) -> Result<(), Fault> {
+        let window = match self.active_window() {
+            Some(window) if !self.editing && self.windows[window - 1].running => window,
+            _ => return Ok(()),
+        };
+        let job = match self.jobs.iter().position(|job| !job.launched) {
+            Some(job) => job,
+            None => {
+                serial_println!("[kernel] no room for another background job");
+                return Ok(());
+            }
+        };
+        self.swap_with_job(window, job);
+        self.windows[window - 1].stop();
+        serial_println!("[kernel] F{}: detached as #{}", window, self.jobs[job].pid);
+        self.buffer_offset = 0;
+        self.file_scroll = 0;
+        self.empty_screen();
+        self.screen = update_screen(self.screen, window);
+        self.add_files(true)
+    }
+
+    // Ticks whichever program the scheduler picks, in a window or in the
+    // background, if any can run. Nothing runs while the fault screen is up.
+    pub fn run_one_instruction(&mut self) {
+        if self.fault.is_some() {
+            return;
         }
-            let program = core::str::from_utf8(&file[0..count]).unwrap();
-            if self.active == 1 {   
-                if !self.q1_run.0 { 
-                    self.q1_int = Interpreter::new(program);
-                    self.q1_run.0 = true;
-                    self.q1_run.2 = true;
-                    self.turn[0] = true;
-                }  
-            } else if self.active == 2 {
-                if !self.q2_run.0 {
-                    self.q2_int = Interpreter::new(program);
-                    self.q2_run.0 = true;
-                    self.q2_run.2 = true;
-                    self.turn[1] = true;
-                }
-            } else if self.active == 3 {
-                if !self.q3_run.0 {
-                    self.q3_int = Interpreter::new(program);
-                    self.q3_run.0 = true;
-                    self.q3_run.2 = true;
-                    self.turn[2] = true;
-                }
-            } else if self.active == 4 {
-                if !self.q4_run.0 {
-                    //self.q4_int = Interpreter::new(program);
-                    self.q4_run.0 = true;
-                    self.turn[3] = true;
-                }
+        let runnable = core::array::from_fn(|i| {
+            let process = self.process(i);
+            process.running && !process.waiting && !process.paused
+        });
+        if let Some(i) = self.scheduler.next(runnable) {
+            self.run_process(i);
+        }
+    }
+
+    pub fn set_quantum(&mut self, ticks: usize) {
+        self.scheduler.set_quantum(ticks);
+    }
+
+    // The selected background job, as long as it is still in the
+    // background.
+    fn selected_job(&self) -> Option<usize> {
+        self.selected_job.filter(|job| self.jobs[*job].launched)
+    }
+
+    // Moves the selection on to the next background job, or back to the
+    // active window after the last one.
+    fn select_next_job(&mut self) {
+        let from = self.selected_job().map_or(0, |job| job + 1);
+        self.selected_job = (from..MAX_JOBS).find(|job| self.jobs[*job].launched);
+        self.draw_proc_status();
+    }
+
+    // The process F7-F9 act on: the selected job, or else the active
+    // window's.
+    fn controlled_slot(&self) -> Option<usize> {
+        match self.selected_job() {
+            Some(job) => Some(NUM_WINDOWS + job),
+            None => self.active_window().map(|window| window - 1),
+        }
+    }
+
+    // Stops or restarts ticking a program. A paused program keeps its
+    // interpreter state and output, and still takes input.
+    fn toggle_pause(&mut self) {
+        if let Some(slot) = self.controlled_slot() {
+            let process = self.process_mut(slot);
+            if process.running {
+                process.paused = !process.paused;
+                let paused = process.paused;
+                serial_println!("[kernel] {}: {}", self.place(slot), if paused {"paused"} else {"resumed"});
+                self.draw_proc_status();
             }
+        }
     }
 
-    pub fn run_one_instruction(&mut self) {
-        if self.q1_run.0 || self.q2_run.0 || self.q3_run.0 || self.q4_run.0 {
-            if self.q1_run.0 && self.turn_index == 1 {
-                if !self.q1_run.1 { //if screen 1 is waiting
-                    if self.input_flag1 {
-                        self.q1_int.provide_input(&self.input1[0..self.input_offset1]);
-                        self.input_offset1 = 0;
-                        self.input_flag1 = false;
-                    } 
-                    let mut output = KernelOutput::new(1, self.screen, self.new_line1);
-                    let result: TickResult<()> = self.q1_int.tick(&mut output);
-                    self.ticks[self.turn_index - 1] += 1;
-                    let tmp = output.return_values();
-                    self.screen = tmp.1;
-                    self.new_line1 = tmp.2;
-                    
-                    match result {
-                        
-                        TickResult::Ok(()) => {
-                        },
-                        TickResult::Finished => {
-                            let s = ['[', 'D', 'O', 'N', 'E', ']'];
-                            for c in s {
-                                self.edit(c, 1);
-                            }
-                            //self.draw();
-                            self.q1_run = (false, false, true);
-                        } ,
-                        TickResult::AwaitInput => {
-                            self.waiting = true;
-                            self.q1_run.1 = true;
-                        },
-                        TickResult::Err(e) => {
-                            println!("{:?}", e);
-                            panic!()
-                        },
-                }
-                self.draw();
-            }    
-            } 
-            if self.q2_run.0 && self.turn_index == 2 {
-                if !self.q2_run.1 { //if screen 1 is waiting
-                    if self.input_flag2 {
-                        self.q2_int.provide_input(&self.input2[0..self.input_offset2]);
-                        self.input_offset2 = 0;
-                        self.input_flag2 = false;
-                    } 
-                    let mut output = KernelOutput::new(2, self.screen, self.new_line2);
-                    let result: TickResult<()> = self.q2_int.tick(&mut output);
-                    self.ticks[self.turn_index - 1] += 1;
-                    let tmp = output.return_values();
-                    self.screen = tmp.1;
-                    self.new_line2 = tmp.2;
-                    
-                    
-                    match result {
-                        
-                        TickResult::Ok(()) => {
-                        },
-                        TickResult::Finished => {
-                            let s = ['[', 'D', 'O', 'N', 'E', ']'];
-                            for c in s {
-                                self.edit(c, 2);
-                            }
-                            //self.draw();
-                            self.q2_run = (false, false, true);
-                        } ,
-                        TickResult::AwaitInput => {
-                            self.waiting = true;
-                            self.q2_run.1 = true;
-                        },
-                        TickResult::Err(e) => {
-                            println!("{:?}", e);
-                            panic!()
-                        },
-                    }
-                    self.draw();
-                }     
-            }
-            if self.q3_run.0 && self.turn_index == 3 {
-                if !self.q3_run.1 { //if screen 1 is waiting
-
-                    if self.input_flag3 {
-                        //println!("{:?}", self.input);
-                        //panic!();
-                        self.q3_int.provide_input(&self.input3[0..self.input_offset3]);
-                        self.input_offset3 = 0;
-                        self.input_flag3 = false;
-                    } 
-                    let mut output = KernelOutput::new(3, self.screen, self.new_line3);
-                    let result: TickResult<()> = self.q3_int.tick(&mut output);
-                    self.ticks[self.turn_index - 1] += 1;
-                    let tmp = output.return_values();
-                    self.screen = tmp.1;
-                    self.new_line3 = tmp.2;
-                   
-                    match result {
-                        
-                        TickResult::Ok(()) => {
-                        },
-                        TickResult::Finished => {
-                            let s = ['[', 'D', 'O', 'N', 'E', ']'];
-                            for c in s {
-                                self.edit(c, 3);
-                            }
-                            //self.draw();
-                            self.q3_run = (false, false, true);
-                        } ,
-                        TickResult::AwaitInput => {
-                            self.waiting = true;
-                            self.q3_run.1 = true;
-                        },
-                        TickResult::Err(e) => {
-                            println!("{:?}", e);
-                            panic!()
-                        },
+    // Gives a program longer (F8) or shorter (F7) turns.
+    fn change_priority(&mut self, raise: bool) {
+        if let Some(slot) = self.controlled_slot() {
+            let priority = self.scheduler.priority(slot);
+            let priority = if raise {priority + 1} else {priority.saturating_sub(1)};
+            self.scheduler.set_priority(slot, priority);
+            self.draw_proc_status();
+        }
+    }
+
+    fn run_process(&mut self, slot: usize) {
+        let place = self.place(slot);
+        let (process, screen) = self.process_and_screen(slot);
+        let mut output = KernelOutput::new(place, screen, &mut process.console);
+        let result: TickResult<()> = process.interp.tick(&mut output);
+        process.ticks += 1;
+
+        match result {
+            TickResult::Ok(()) => {
+            },
+            TickResult::Finished => {
+                output.print(b"[DONE]\n");
+                process.running = false;
+                process.waiting = false;
+            } ,
+            TickResult::AwaitInput => {
+                process.waiting = true;
+            },
+            TickResult::Err(e) => {
+                // Only this program stops; the others carry on.
+                if !output.at_line_start() {
+                    output.print(b"\n");
                 }
-                self.draw();
-    
-            }     
-            }
-            if self.q4_run.0 && self.turn_index == 4 {
-                    if !self.q4_run.1 { //if screen 1 is waiting
-                        if self.input_flag1 {
-                            //self.q4_int.provide_input(&self.input[0..self.input_offset]);
-                            self.input_offset1 = 0;
-                            self.input_flag1 = false;
-                        } 
-                        let mut output = KernelOutput::new(self.active, self.screen, self.new_line);
-                        //let result: TickResult<()> = self.q4_int.tick(&mut output);
-                        self.ticks[self.turn_index] += 1;
-                        let tmp = output.return_values();
-                        self.screen = tmp.1;
-                        self.new_line = tmp.2;
-                        self.draw();
-        
-                    //     match result {
-                            
-                    //         TickResult::Ok(()) => {
-                    //         },
-                    //         TickResult::Finished => {
-                    //             let s = ['[', 'D', 'O', 'N', 'E', ']'];
-                    //             for c in s {
-                    //                 self.edit(c);
-                    //             }
-                    //             self.draw();
-                    //             self.q4_run = (false, false);
-                    //         } ,
-                    //         TickResult::AwaitInput => {
-                    //             self.waiting = true;
-                    //             self.q4_run.1 = true;
-                    //         },
-                    //         TickResult::Err(e) => {
-                    //             println!("{:?}", e);
-                    //             panic!()
-                    //         },
-                    // }
-                }   
-                self.turn_index += 1;   
-            } 
-            self.turn_index += 1;
-            self.turn_index = self.turn_index % 4;
-        }
-        
-        
-
-    }
-
-    fn handle_unicode(&mut self, key: char) {
+                let _ = writeln!(output, "[ERROR] {:?}", e);
+                process.running = false;
+                process.waiting = false;
+                process.failed = true;
+                serial_println!("[kernel] {}: {:?}", place, e);
+            },
+        }
+        if process.waiting {
+            self.deliver_input(slot);
+        }
+        // Background jobs have nothing on screen to redraw.
+        if let Place::Window(_) = place {
+            self.draw();
+        }
+    }
+
+    // Queues the line typed for a window's program, handing it over straight
+    // away if the program is waiting for it. When the queue is full the line
+    // stays where it is.
+    fn enter_input(&mut self, window: usize) {
+        let process = &mut self.windows[window - 1];
+        process.console.view_offset = 0;
+        if process.queue.push(process.input.text()) {
+            process.input.submit();
+        }
+        if process.waiting {
+            self.deliver_input(window - 1);
+        }
+    }
+
+    // Gives a waiting program the oldest line queued for it, echoed after
+    // its prompt as though it had just been typed.
+    fn deliver_input(&mut self, slot: usize) {
+        let place = self.place(slot);
+        let (process, screen) = self.process_and_screen(slot);
+        if let Some(line) = process.queue.pop() {
+            let mut output = KernelOutput::new(place, screen, &mut process.console);
+            for c in line {
+                output.print(&[*c as u8]);
+            }
+            output.print(b"\n");
+            process.interp.provide_input(line);
+            process.waiting = false;
+        }
+    }
+
+    fn handle_unicode(&mut self, key: char) -> Result<(), Fault> {
+        if let Some(prompt) = self.file_prompt {
+            return self.answer_file_prompt(prompt, key);
+        }
         let mut activate = false;
-        if !self.editing && self.active != 5 && (!self.q1_run.0 && !self.q2_run.0 && !self.q3_run.0 && !self.q4_run.0) {
-            if key == 'e' {
-                self.read_file_to_window();
-                activate = true;
-            } else{
-                activate = false;
-            }
-            
-        } 
-        
-        if !self.q1_run.0 || !self.q2_run.0 || !self.q3_run.0 || !self.q4_run.0 {
-            if key == 'r' {
-                self.run();
-                activate = true;
+        if let Some(window) = self.active_window() {
+            if !self.editing && !self.windows[window - 1].takes_input() {
+                if key == 'e' {
+                    self.read_file_to_window()?;
+                    activate = true;
+                } else if key == 'r' {
+                    self.run()?;
+                    activate = true;
+                } else if key == 'b' && self.shows_files(window) {
+                    self.run_in_background()?;
+                    activate = true;
+                } else if key == 'a' {
+                    self.start_attach_prompt(window);
+                    activate = true;
+                } else if key == 'c' && self.shows_files(window) {
+                    self.start_shell(window);
+                    activate = true;
+                } else if key == 'd' && self.shows_files(window) {
+                    self.start_file_prompt(PromptKind::Delete);
+                    activate = true;
+                } else if key == 'n' && self.shows_files(window) {
+                    self.start_file_prompt(PromptKind::Rename);
+                    activate = true;
+                } else if key == 's' && self.shows_files(window) {
+                    self.show_sizes = !self.show_sizes;
+                    self.buffer_offset = 0;
+                    self.file_scroll = 0;
+                    self.add_files(true)?;
+                    activate = true;
+                }
             }
-        
         }
 
         if !activate {
-            if is_drawable(key) && self.waiting && key != '\n' {
-                self.edit(key, self.active);
-                if self.active == 1{
-                    self.input1[self.input_offset1] = key;
-                    self.input_offset1 += 1;
-                } else if self.active == 2{
-                    self.input2[self.input_offset2] = key;
-                    self.input_offset2 += 1;
-                } else if self.active == 3{
-                    self.input3[self.input_offset3] = key;
-                    self.input_offset3 += 1;
+            let typing = match self.active_window() {
+                Some(window) => self.windows[window - 1].takes_input(),
+                None => false,
+            };
+            if typing && key != '\n' && (is_drawable(key) || key == '\u{08}' || key == '\u{7f}') {
+                let process = &mut self.windows[self.active - 1];
+                process.console.view_offset = 0;
+                match key {
+                    '\u{08}' => process.input.backspace(),
+                    '\u{7f}' => process.input.delete(),
+                    _ => process.input.insert(key),
                 }
-                
-
             } else if key.is_alphanumeric() && self.active == 5{
-                let start = FILENAME_PROMPT.len();
-                let mut count: usize = 0; 
-                for i in start..start+MAX_FILENAME_BYTES {
-                    if count == MAX_FILENAME_BYTES {
-                        break;
-                    }
-                    if self.screen[0][i] == ' '{
-                        self.screen[0][i] = key;
-                        break;
-                    }
-                    count += 1
-                }
-                
-            }else if is_drawable(key) && self.editing {
-                self.edit(key, self.active)
+                self.type_into_prompt(FILENAME_PROMPT.len(), key);
+            }else if (is_drawable(key) || key == '\u{7f}') && self.editing {
+                self.edit(key)
             } else if key == '\u{08}'{
                 if self.active == 5 {
-                    let start = FILENAME_PROMPT.len();
-                    for i in start..start+MAX_FILENAME_BYTES {
-                        if self.screen[0][i] == ' '{
-                            self.screen[0][i - 1] = ' ';
-                            break;
-                        }
-                    }
+                    self.backspace_prompt(FILENAME_PROMPT.len());
                 } else if self.editing{
-                    self.edit(key, self.active)
+                    self.edit(key)
                 }
-                
+
             } else if key == '\n'{
                 if self.active == 5 {
-                    self.create_file();
+                    self.create_file()?;
                 }
                 if self.editing {
-                    self.edit(key, self.active);
+                    self.edit(key);
                 }
-                if self.waiting {      
-                    if self.active == 1 {
-                        self.new_line1 = true;
-                        self.q1_run.1 = false;
-                        self.input_flag1 = true;
-                    } else if self.active == 2 {
-                        self.new_line2 = true;
-                        self.q2_run.1 = false;
-                        self.input_flag2 = true;
-                    } else if self.active == 3 {
-                        self.new_line3 = true;
-                        self.q3_run.1 = false;
-                        self.input_flag3 = true;
-                    } else if self.active == 4 {
-                        self.q4_run.1 = false;
-                    }
-                    self.waiting_check();
-                    //self.waiting = false;
+                if typing && self.windows[self.active - 1].shell {
+                    self.enter_command(self.active)?;
+                } else if typing {
+                    self.enter_input(self.active);
                 }
-                
-            } 
+
+            }
         }
+        Ok(())
     }
-        
-    fn waiting_check(&mut self){
-        if !self.q1_run.1 && !self.q2_run.1 && !self.q3_run.1 && self.q4_run.1{   // add the rest 
-            self.waiting = false
+
+    fn highlight(&mut self, dir: char) -> Result<(), Fault> {
+        let file_count = self.files.list_directory().or_fault("listing files")?.0;
+        let target = match dir {
+            'r' => Some(self.buffer_offset + 1),
+            'l' => self.buffer_offset.checked_sub(1),
+            'u' => self.buffer_offset.checked_sub(self.file_columns()),
+            'd' => Some(self.buffer_offset + self.file_columns()),
+            _ => None,
+        };
+        if let Some(target) = target {
+            if target < file_count {
+                self.move_highlight(target)?;
+            }
         }
+        Ok(())
     }
 
-    fn highlight(&mut self, dir: char){
-        let directory = self.files.list_directory().unwrap();
-        let file_count = directory.0;
-        if !self.editing {
-            if dir == 'r' && self.buffer_offset < file_count - 1{
-                self.buffer_offset += 1;
-                self.move_highlight();
-            } else if dir == 'l' && self.buffer_offset != 0{
-                self.buffer_offset -= 1;
-                self.move_highlight();
-            } else if dir == 'u' && self.buffer_offset > 2{
-                self.buffer_offset -= 3;
-                self.move_highlight();
-            }else if dir == 'd' && self.buffer_offset < file_count - 3{
-                self.buffer_offset += 3;
-                self.move_highlight();
-            }
-        }
-        
-    }
-
-    fn move_highlight(&mut self) {
-        if self.active == 1{
-            let start = 1 + (self.buffer_offset % 3 * (MAX_FILENAME_BYTES + 1));
-            let buff = self.screen[self.buffer_offset / 3 + 2];
-            let mut count = 0;
-            for i in start..start + MAX_FILENAME_BYTES{
-                self.q1_buffer[count] = buff[i];
-                count += 1;
-            }
-        } else if self.active == 2 {
-            let start = WINDOW_WIDTH + 3 + (self.buffer_offset % 3 * (MAX_FILENAME_BYTES + 1));
-            let buff = self.screen[self.buffer_offset / 3 + 2];
-            let mut count = 0;
-            for i in start..start + MAX_FILENAME_BYTES{
-                self.q2_buffer[count] = buff[i];
-                count += 1;
-            }
-        } else if self.active == 3 {
-            let start = 1 + (self.buffer_offset % 3 * (MAX_FILENAME_BYTES + 1));
-            let buff = self.screen[self.buffer_offset / 3 + 3 + WINDOW_HEIGHT];
-            let mut count = 0;
-            for i in start..start + MAX_FILENAME_BYTES{
-                self.q3_buffer[count] = buff[i];
-                count += 1;
-            }
-        } else if self.active == 4 {
-            let start = WINDOW_WIDTH + 3 + (self.buffer_offset % 3 * (MAX_FILENAME_BYTES + 1));
-            let buff = self.screen[self.buffer_offset / 3 + 3 + WINDOW_HEIGHT];
-            let mut count = 0;
-            for i in start..start + MAX_FILENAME_BYTES{
-                self.q4_buffer[count] = buff[i];
-                count += 1;
-            }
+    fn page_files(&mut self, down: bool) -> Result<(), Fault> {
+        let file_count = self.files.list_directory().or_fault("listing files")?.0;
+        if file_count == 0 {
+            return Ok(());
         }
+        let page = FILE_ROWS * self.file_columns();
+        let target = if down {
+            (self.buffer_offset + page).min(file_count - 1)
+        } else {
+            self.buffer_offset.saturating_sub(page)
+        };
+        self.move_highlight(target)
     }
-    
+
+    // Highlights file number `target` in the active window, scrolling the
+    // list if it is off screen.
+    fn move_highlight(&mut self, target: usize) -> Result<(), Fault> {
+        let window = match self.active_window() {
+            Some(window) if self.shows_files(window) => window,
+            _ => return Ok(()),
+        };
+        let (file_count, filenames) = self.files.list_directory().or_fault("listing files")?;
+        self.buffer_offset = target;
+        for (c, byte) in self.windows[window - 1].filename.iter_mut().zip(filenames[target]) {
+            *c = byte as char;
+        }
+        let row = target / self.file_columns();
+        if row < self.file_scroll {
+            self.file_scroll = row;
+        } else if row >= self.file_scroll + FILE_ROWS {
+            self.file_scroll = row + 1 - FILE_ROWS;
+        } else {
+            return Ok(());
+        }
+        self.draw_file_list(window, file_count, &filenames);
+        Ok(())
+    }
+
     pub fn draw(&mut self) {
-        self.add_files(false);
+        if self.fault.is_none() {
+            let result = self.add_files(false);
+            self.catch(result);
+        }
+        if self.fault.is_some() {
+            self.draw_fault();
+            return;
+        }
+        if self.editing {
+            self.draw_editor(self.active);
+        }
+        for window in 1..=NUM_WINDOWS {
+            let process = &mut self.windows[window - 1];
+            if process.takes_input() {
+                // Typing shows up where the next output would, so move there
+                // first, scrolling if need be.
+                KernelOutput::new(Place::Window(window), &mut self.screen, &mut process.console).next_spot();
+            }
+        }
         for i in 0..BUFFER_HEIGHT{
             for j in 0..BUFFER_WIDTH{
-                plot(self.screen[i][j], j, i, ColorCode::new(Color::White, Color::Black));
+                self.display.plot(self.screen[i][j], j, i, ColorCode::new(Color::White, Color::Black));
             }
         }
-        
+
         if self.editing {
             self.setup_editing_window();
+            self.draw_editor_cursor(self.active);
         }
         self.draw_highlight();
-        
-        
+        for window in 1..=NUM_WINDOWS {
+            if self.windows[window - 1].takes_input() {
+                self.draw_input_line(window);
+            }
+            if self.windows[window - 1].console.view_offset > 0 {
+                self.draw_scrollback(window);
+            }
+            if self.windows[window - 1].running && self.windows[window - 1].paused {
+                self.draw_paused(window);
+            }
+            if !self.windows[window - 1].queue.is_empty() {
+                self.draw_queued(window);
+            }
+        }
+        self.draw_selected_job();
+
+
     }
 
-    fn draw_highlight(&mut self) {
-        for i in 0..MAX_FILENAME_BYTES + 1{
-            if self.active == 1{
-                if !self.editing && !self.q1_run.2 {
-                    plot(self.q1_buffer[i], i + 1 + (self.buffer_offset % 3 * (MAX_FILENAME_BYTES + 1)), self.buffer_offset / 3 + 2, ColorCode::new(Color::Black, Color::White));
+    // Turns a window showing the files into a command shell.
+    fn start_shell(&mut self, window: usize) {
+        self.windows[window - 1].shell = true;
+        self.clear_window(window);
+        let mut output = self.shell_output(window);
+        output.print(b"Shell - type help for commands\n");
+        output.print(SHELL_PROMPT);
+    }
+
+    // Output to the shell in `window`.
+    fn shell_output(&mut self, window: usize) -> KernelOutput<'_> {
+        KernelOutput::new(Place::Window(window), &mut self.screen, &mut self.windows[window - 1].console)
+    }
+
+    // Echoes the line typed in a shell window after its prompt, runs it as a
+    // command and prompts for the next one. Anything but ASCII is typed as
+    // '?', since file names are bytes.
+    fn enter_command(&mut self, window: usize) -> Result<(), Fault> {
+        let process = &mut self.windows[window - 1];
+        process.console.view_offset = 0;
+        let mut line = [0; MAX_INPUT_CHARS];
+        let len = process.input.text().len();
+        for (i, c) in process.input.text().iter().enumerate() {
+            line[i] = if c.is_ascii() {*c as u8} else {b'?'};
+        }
+        process.input.submit();
+        let mut output = self.shell_output(window);
+        output.print(&line[0..len]);
+        output.print(b"\n");
+        let line = from_utf8(&line[0..len]).or_fault("reading a command")?;
+        match shell::parse(line) {
+            Ok(Some(command)) => self.run_command(window, command)?,
+            Ok(None) => {}
+            Err(ParseError::Usage(usage)) => {
+                let _ = writeln!(self.shell_output(window), "usage: {}", usage);
+            }
+            Err(ParseError::Unknown(name)) => {
+                let _ = writeln!(self.shell_output(window), "{}: unknown command", name);
+            }
+        }
+        if self.windows[window - 1].shell {
+            self.shell_output(window).print(SHELL_PROMPT);
+        }
+        Ok(())
+    }
+
+    // Carries out a shell command, printing what it shows, or why it can't
+    // be done, in the shell's window.
+    fn run_command(&mut self, window: usize, command: Command) -> Result<(), Fault> {
+        match command {
+            Command::Ls => {
+                let (count, filenames, sizes) = self.sized_files()?;
+                let mut out = self.shell_output(window);
+                for f in 0..count {
+                    let name = from_utf8(trim_filename(&filenames[f])).or_fault("listing files")?;
+                    let _ = writeln!(out, "{:<11}{:>5} B", name.trim_end(), sizes[f]);
                 }
-                if !self.q2_run.2{
-                    plot(self.q2_buffer[i], i + 1 + WINDOW_WIDTH + 2, 2, ColorCode::new(Color::Black, Color::White));
+            }
+            Command::Cat(name) => {
+                if let Some(entry) = self.find_file(window, name)? {
+                    let mut contents = [0; MAX_FILE_BYTES];
+                    let len = self.read_file(from_utf8(trim_filename(&entry)).or_fault("naming a file")?, &mut contents)?;
+                    let mut out = self.shell_output(window);
+                    out.print(&contents[0..len]);
+                    if len > 0 && contents[len - 1] != b'\n' {
+                        out.print(b"\n");
+                    }
                 }
-                if !self.q3_run.2{
-                    plot(self.q3_buffer[i], i + 1, WINDOW_HEIGHT + 3, ColorCode::new(Color::Black, Color::White));
+            }
+            Command::Rm(name) => {
+                if let Some(entry) = self.find_file(window, name)? {
+                    self.rewrite_files(trim_filename(&entry), None)?;
+                    self.files_changed()?;
                 }
-                plot(self.q4_buffer[i], i + WINDOW_WIDTH + 3, WINDOW_HEIGHT + 3, ColorCode::new(Color::Black, Color::White));
-            }else if self.active == 2 {
-                if !self.q1_run.2{
-                    plot(self.q1_buffer[i], i + 1, 2, ColorCode::new(Color::Black, Color::White));
-                } 
-                if !self.editing && !self.q2_run.2 {
-                    plot(self.q2_buffer[i], i + WINDOW_WIDTH + 3 + (self.buffer_offset % 3 * (MAX_FILENAME_BYTES + 1)),self.buffer_offset / 3 + 2, ColorCode::new(Color::Black, Color::White));
+            }
+            Command::Mv(from, to) => {
+                if let Some(entry) = self.find_file(window, from)? {
+                    if self.check_new_name(window, to)? {
+                        self.rewrite_files(trim_filename(&entry), Some(to.as_bytes()))?;
+                        self.files_changed()?;
+                    }
                 }
-                if !self.q3_run.2 {
-                    plot(self.q3_buffer[i], i + 1, WINDOW_HEIGHT + 3, ColorCode::new(Color::Black, Color::White));
+            }
+            Command::Cp(from, to) => {
+                if let Some(entry) = self.find_file(window, from)? {
+                    if self.check_new_name(window, to)? {
+                        self.copy_file(window, trim_filename(&entry), to)?;
+                    }
                 }
-                plot(self.q4_buffer[i], i + WINDOW_WIDTH + 3, WINDOW_HEIGHT + 3, ColorCode::new(Color::Black, Color::White));
-            } else if self.active == 3 {
-                if !self.q1_run.2{
-                    plot(self.q1_buffer[i], i + 1, 2, ColorCode::new(Color::Black, Color::White));
-                } 
-                if !self.q2_run.2 {
-                    plot(self.q2_buffer[i], i + 1 + WINDOW_WIDTH + 2, 2, ColorCode::new(Color::Black, Color::White));
+            }
+            Command::Run(name, target) => {
+                if let Some(entry) = self.find_file(window, name)? {
+                    self.run_from_shell(window, &entry, target)?;
                 }
-                if !self.editing && !self.q3_run.2 {
-                    plot(self.q3_buffer[i], i + 1 + (self.buffer_offset % 3 * (MAX_FILENAME_BYTES + 1)),self.buffer_offset / 3 + 3 + WINDOW_HEIGHT, ColorCode::new(Color::Black, Color::White));
+            }
+            Command::Kill(pid) => self.kill(window, pid)?,
+            Command::Ps => {
+                let _ = writeln!(self.shell_output(window), "PID  WHERE STATE    PROGRAM");
+                for slot in 0..NUM_PROCESSES {
+                    let process = self.process(slot);
+                    if !process.launched {
+                        continue;
+                    }
+                    let (pid, state, filename, len) = (process.pid, process.state(), process.filename, process.filename_len());
+                    let mut out = self.shell_output(window);
+                    let _ = write!(out, "{:<5}", pid);
+                    let _ = if slot < NUM_WINDOWS {write!(out, "F{:<5}", slot + 1)} else {write!(out, "{:<6}", "bg")};
+                    let _ = write!(out, "{:<9}", state.name());
+                    for c in &filename[0..len] {
+                        let _ = out.write_char(*c);
+                    }
+                    let _ = writeln!(out);
                 }
-                plot(self.q4_buffer[i], i + WINDOW_WIDTH + 3, WINDOW_HEIGHT + 3, ColorCode::new(Color::Black, Color::White));
-            } else if self.active == 4 {
-                plot(self.q1_buffer[i], i + 1, 2, ColorCode::new(Color::Black, Color::White));
-                plot(self.q2_buffer[i], i + 1 + WINDOW_WIDTH + 2, 2, ColorCode::new(Color::Black, Color::White));
-                plot(self.q3_buffer[i], i+ 1, WINDOW_HEIGHT + 3, ColorCode::new(Color::Black, Color::White));
-                if !self.editing && !self.q4_run.2 {
-                    plot(self.q4_buffer[i], i  +WINDOW_WIDTH + 3 + (self.buffer_offset % 3 * (MAX_FILENAME_BYTES + 1)), self.buffer_offset / 3 + WINDOW_HEIGHT + 3, ColorCode::new(Color::Black, Color::White));
+            }
+            Command::Df => {
+                let (count, _) = self.files.list_directory().or_fault("listing files")?;
+                self.measure_usage()?;
+                let used = self.blocks_used;
+                let _ = writeln!(self.shell_output(window), "{}/{} blocks  {}/{} files", used, NUM_BLOCKS, count, MAX_FILES_STORED);
+            }
+            Command::Help => {
+                let mut out = self.shell_output(window);
+                for (usage, what) in shell::HELP {
+                    let _ = writeln!(out, "{:<14}{}", usage, what);
                 }
-               
             }
+            Command::Exit => {
+                self.windows[window - 1].stop();
+                self.clear_window(window);
+                self.files_changed()?;
+            }
+        }
+        Ok(())
+    }
+
+    // The files in directory order, with the size of each.
+    fn sized_files(&mut self) -> Result<SizedFiles, Fault> {
+        let (count, filenames) = self.files.list_directory().or_fault("listing files")?;
+        let mut sizes = [0; MAX_FILES_STORED];
+        let mut contents = [0; MAX_FILE_BYTES];
+        for f in 0..count {
+            sizes[f] = self.read_file(from_utf8(trim_filename(&filenames[f])).or_fault("listing files")?, &mut contents)?;
         }
+        Ok((count, filenames, sizes))
     }
 
-    fn tick_numbers(&mut self, tick_num: usize) -> (char, char, char, char) {
-        let mut spot1 = '0';
-        let mut spot2 = '0';
-        let mut spot3 = '0';
-        let mut spot4 = '0';
-        let mut count = 4;
+    // The directory entry for the file a shell command names, which matches
+    // with or without the spaces F5 pads names with. Says so in the shell
+    // if there is no such file.
+    fn find_file(&mut self, window: usize, name: &str) -> Result<Option<[u8; MAX_FILENAME_BYTES]>, Fault> {
+        let (count, filenames) = self.files.list_directory().or_fault("listing files")?;
+        let entry = filenames[0..count].iter().find(|f| trim_filename(*f).trim_ascii_end() == name.as_bytes());
+        if entry.is_none() {
+            let _ = writeln!(self.shell_output(window), "{}: no such file", name);
+        }
+        Ok(entry.copied())
+    }
 
-        
-        
-        spot4 = char::from_digit((self.ticks[tick_num] % 10) as u32, 10).unwrap();
-        spot3 =  char::from_digit((self.ticks[tick_num] / 10 % 10)as u32, 10).unwrap();
-        spot2 = char::from_digit((self.ticks[tick_num]/ 10 / 10 % 10) as u32, 10).unwrap();
-        spot1 = char::from_digit((self.ticks[tick_num]/ 10 / 10 / 10 % 10) as u32, 10).unwrap();
-        let mut spots = [spot1, spot2, spot3, spot4];
-        //
-        for s in spots {
-            if s != '0' || count == 1{
-                break;
-            } else {
-                count -= 1;
+    // Whether a file can be given `name`, saying why not in the shell if not.
+    fn check_new_name(&mut self, window: usize, name: &str) -> Result<bool, Fault> {
+        let problem = if name.len() > MAX_FILENAME_BYTES {
+            "name too long"
+        } else if self.file_exists(name.as_bytes())? {
+            "file exists"
+        } else {
+            return Ok(true);
+        };
+        let _ = writeln!(self.shell_output(window), "{}: {}", name, problem);
+        Ok(false)
+    }
+
+    // Copies file `from` to a new file `to`. Running out of room is reported
+    // in the shell rather than as a fault.
+    fn copy_file(&mut self, window: usize, from: &[u8], to: &str) -> Result<(), Fault> {
+        let mut contents = [0; MAX_FILE_BYTES];
+        let len = self.read_file(from_utf8(from).or_fault("naming a file")?, &mut contents)?;
+        let result = self.files.open_create(to).and_then(|fd| {
+            let written = if len > 0 {self.files.write(fd, &contents[0..len]).map(|_| ())} else {Ok(())};
+            self.files.close(fd).and(written)
+        });
+        match result {
+            Ok(()) => serial_println!("[kernel] copied {} to {}", from_utf8(from).or_fault("naming a file")?, to),
+            Err(e) => {
+                let _ = writeln!(self.shell_output(window), "cp: {:?}", e);
+            }
+        }
+        self.sync_file(to.as_bytes())?;
+        self.files_changed()
+    }
+
+    // Runs a file in window `target`, which has to be showing the files, or
+    // else as a background job, and says where it went.
+    fn run_from_shell(&mut self, window: usize, entry: &[u8; MAX_FILENAME_BYTES], target: Option<usize>) -> Result<(), Fault> {
+        let mut filename = [' '; MAX_FILENAME_BYTES + 1];
+        for i in 0..MAX_FILENAME_BYTES {
+            filename[i] = entry[i] as char;
+        }
+        let slot = match target {
+            Some(target) if target == 0 || target > NUM_WINDOWS => {
+                let _ = writeln!(self.shell_output(window), "no window F{}", target);
+                return Ok(());
+            }
+            Some(target) if !self.shows_files(target) => {
+                let _ = writeln!(self.shell_output(window), "F{} is busy", target);
+                return Ok(());
+            }
+            Some(target) => {
+                self.clear_window(target);
+                target - 1
+            }
+            None => match self.jobs.iter().position(|job| !job.launched) {
+                Some(job) => NUM_WINDOWS + job,
+                None => {
+                    let _ = writeln!(self.shell_output(window), "no room for another job");
+                    return Ok(());
+                }
+            },
+        };
+        self.launch(slot, filename)?;
+        let (pid, place) = (self.process(slot).pid, self.place(slot));
+        match place {
+            Place::Window(_) => {
+                let _ = writeln!(self.shell_output(window), "#{} in {}", pid, place);
+            }
+            Place::Job(_) => {
+                let _ = writeln!(self.shell_output(window), "#{} in the background", pid);
             }
         }
-        
-        if count == 3 {
-            spot1 = spot2;
-            spot2 = spot3;
-            spot3 = spot4;
-            spot4 = ' ';
-        } else if count == 2 {
-            spot1 = spot3;
-            spot2 = spot4;
-            spot3 = ' ';
-            spot4 = ' ';
-        } else if count == 1 {
-            spot1 = spot4;
-            spot2 = ' ';
-            spot3 = ' ';
-            spot4 = ' ';
+        Ok(())
+    }
+
+    // Stops the program with process ID `pid`, wherever it is. A window it
+    // was in goes back to showing the files.
+    fn kill(&mut self, window: usize, pid: usize) -> Result<(), Fault> {
+        let slot = match (0..NUM_PROCESSES).find(|slot| self.process(*slot).launched && self.process(*slot).pid == pid) {
+            Some(slot) => slot,
+            None => {
+                let _ = writeln!(self.shell_output(window), "#{}: no such process", pid);
+                return Ok(());
+            }
+        };
+        serial_println!("[kernel] {}: killed", self.place(slot));
+        self.process_mut(slot).stop();
+        if slot < NUM_WINDOWS {
+            self.clear_window(slot + 1);
+            self.files_changed()?;
         }
-        return (spot1, spot2, spot3, spot4)
+        let _ = writeln!(self.shell_output(window), "killed #{}", pid);
+        Ok(())
     }
 
-    pub fn draw_proc_status(&mut self) {
-        self.screen[0][WINDOWS_WIDTH + 1] = 'F';
-        self.screen[0][WINDOWS_WIDTH + 2] = '1';
-        let ticks1 = self.tick_numbers(0);
-        self.screen[1][WINDOWS_WIDTH + 1] = ticks1.0;
-        self.screen[1][WINDOWS_WIDTH + 2] = ticks1.1;
-        self.screen[1][WINDOWS_WIDTH + 3] = ticks1.2;
-        self.screen[1][WINDOWS_WIDTH + 4] = ticks1.3;
-
-        self.screen[2][WINDOWS_WIDTH + 1] = 'F';
-        self.screen[2][WINDOWS_WIDTH + 2] = '2';
-        let ticks2 = self.tick_numbers(1);
-        self.screen[3][WINDOWS_WIDTH + 1] = ticks2.0;
-        self.screen[3][WINDOWS_WIDTH + 2] = ticks2.1;
-        self.screen[3][WINDOWS_WIDTH + 3] = ticks2.2;
-        self.screen[3][WINDOWS_WIDTH + 4] = ticks2.3;
-
-        self.screen[4][WINDOWS_WIDTH + 1] = 'F';
-        self.screen[4][WINDOWS_WIDTH + 2] = '3';
-        let ticks3 = self.tick_numbers(2);
-        self.screen[5][WINDOWS_WIDTH + 1] = ticks3.0;
-        self.screen[5][WINDOWS_WIDTH + 2] = ticks3.1;
-        self.screen[5][WINDOWS_WIDTH + 3] = ticks3.2;
-        self.screen[5][WINDOWS_WIDTH + 4] = ticks3.3;
-
-        self.screen[6][WINDOWS_WIDTH + 1] = 'F';
-        self.screen[6][WINDOWS_WIDTH + 2] = '4';
-        let ticks4 = self.tick_numbers(3);
-        self.screen[7][WINDOWS_WIDTH + 1] = ticks4.0;
-        self.screen[7][WINDOWS_WIDTH + 2] = ticks4.1;
-        self.screen[7][WINDOWS_WIDTH + 3] = ticks4.2;
-        self.screen[7][WINDOWS_WIDTH + 4] = ticks4.3;
-
-        for i in 0..NUM_WINDOWS * 2 {
-            for j in WINDOWS_WIDTH.. WINDOWS_WIDTH + TASK_MANAGER_WIDTH {
-                plot(self.screen[i][j], j, i, ColorCode::new(Color::White, Color::Black))
+    // Plots a window as it looked `view_offset` rows ago, over the live rows.
+    fn draw_scrollback(&mut self, window: usize) {
+        let rows = window_rows(window);
+        let cols = window_cols(window);
+        let console = &self.windows[window - 1].console;
+        let first = console.history_len - console.view_offset;
+        for r in 0..rows.len() {
+            let line = first + r;
+            for c in 0..cols.len() {
+                let ch = if line < console.history_len {
+                    console.history_line(line)[c] as char
+                } else {
+                    self.screen[rows.start + line - console.history_len][cols.start + c]
+                };
+                self.display.plot(ch, cols.start + c, rows.start + r, text_color());
             }
         }
+        let indicator = "[SCROLLED]";
+        let start = border_col(window) + MID_WIDTH - indicator.len() - 1;
+        for (i, c) in indicator.chars().enumerate() {
+            self.display.plot(c, start + i, header_row(window), highlight_color());
+        }
     }
 
-    
-}
+    // Plots the line being typed for a running program after its output,
+    // scrolled sideways to keep the cursor in view, with the cursor shown in
+    // the active window. The line only becomes part of the output once the
+    // program takes it.
+    fn draw_input_line(&mut self, window: usize) {
+        let process = &self.windows[window - 1];
+        let row = window_rows(window).start + process.console.cursor.0;
+        let start = window_cols(window).start + process.console.cursor.1;
+        let end = window_cols(window).end;
+        let first = process.input.view_start(end - start);
+        for col in start..end {
+            let i = first + col - start;
+            let c = process.input.text().get(i).copied().unwrap_or(' ');
+            let color = if window == self.active && i == process.input.cursor() {highlight_color()} else {text_color()};
+            self.display.plot(c, col, row, color);
+        }
+    }
 
-pub struct  KernelOutput {
-    active : usize,
-    screen : [[char; BUFFER_WIDTH]; BUFFER_HEIGHT],
-    new_line: bool,
+    // Shows in the header how many lines are typed ahead of the program.
+    fn draw_queued(&mut self, window: usize) {
+        let mut label = [' '; 4];
+        let _ = write!(RowWriter::new(&mut label), "[+{}]", self.windows[window - 1].queue.len());
+        for (i, c) in label.iter().enumerate() {
+            self.display.plot(*c, border_col(window) + WINDOW_WIDTH / 2 + 3 + i, header_row(window), highlight_color());
+        }
+    }
 
-}
+    fn draw_fault(&mut self) {
+        for i in 0..BUFFER_HEIGHT {
+            for j in 0..BUFFER_WIDTH {
+                self.display.plot(self.screen[i][j], j, i, fault_color());
+            }
+        }
+    }
 
-impl KernelOutput{
-    fn new(active: usize, screen: [[char; BUFFER_WIDTH]; BUFFER_HEIGHT] , new_line: bool) -> Self{
-        let mut active = active;
-        let mut screen = screen;
-        let mut new_line = new_line;
-        Self{active, screen, new_line}
+    // Replaces the screen with the fault report and sends the report to
+    // serial as well.
+    fn write_fault_screen(&mut self) {
+        let mut screen = [[' '; BUFFER_WIDTH]; BUFFER_HEIGHT];
+        let _ = self.write_fault_report(&mut PageWriter::new(&mut screen));
+        let _ = self.write_fault_report(&mut serial::SerialWriter);
+        self.screen = screen;
     }
 
-    fn return_values(&mut self) -> (usize, [[char; BUFFER_WIDTH]; BUFFER_HEIGHT], bool){
-        (self.active, self.screen, self.new_line)
+    // What went wrong and where, and what each window and background job was
+    // doing at the time.
+    fn write_fault_report(&self, out: &mut impl Write) -> core::fmt::Result {
+        let fault = match &self.fault {
+            Some(fault) => fault,
+            None => return Ok(()),
+        };
+        writeln!(out, "KERNEL FAULT")?;
+        writeln!(out)?;
+        writeln!(out, "{}", fault.message())?;
+        writeln!(out, "at {}:{}:{}", fault.file(), fault.line(), fault.column())?;
+        writeln!(out)?;
+        match self.active_window() {
+            Some(window) => writeln!(out, "Active window: F{}", window)?,
+            None => writeln!(out, "Active window: none (F5 filename prompt)")?,
+        }
+        writeln!(out)?;
+        writeln!(out, "Where   Program     State     Ticks")?;
+        for slot in 0..NUM_PROCESSES {
+            let process = self.process(slot);
+            if slot >= NUM_WINDOWS && !process.launched {
+                continue;
+            }
+            let len = if process.launched {process.filename_len().min(MAX_FILENAME_BYTES)} else {0};
+            let mut place = [' '; 8];
+            let _ = write!(RowWriter::new(&mut place), "{}", self.place(slot));
+            for c in place.iter().chain(&process.filename[0..len]) {
+                out.write_char(*c)?;
+            }
+            writeln!(out, "{:pad$}{:<10}{}", "", process.state().name(), process.ticks, pad = 12 - len)?;
+        }
+        writeln!(out)?;
+        writeln!(out, "Press R to restart the kernel.")
     }
-}
 
-impl InterpreterOutput for KernelOutput{
-    fn print(&mut self, chars: &[u8]) {
-        let mut last_char = false;
-        let mut spot = (0,0);    
-        if self.active == 1 {
-            for i in FIRST_BORDER_ROW + 1 .. MID_HEIGHT {
-                for j in 1..MID_WIDTH{
-                    if self.screen[i][j] == ' ' && !last_char {
-                        spot = (i, j); 
-                        last_char = true;
-                    }
-                    if self.screen[i][j] != ' ' && last_char {
-                        last_char = false;
-                    }
-                }
+    // Marks a paused window past the queued input count, clear of the
+    // editing label and filename at the start of the header.
+    fn draw_paused(&mut self, window: usize) {
+        for (i, c) in "[PAUSED]".chars().enumerate() {
+            self.display.plot(c, paused_col(window) + i, header_row(window), highlight_color());
+        }
+    }
 
+    fn draw_highlight(&mut self) {
+        if self.active_window().is_none() {
+            return;
+        }
+        for window in 1..=NUM_WINDOWS {
+            if !self.shows_files(window) {
+                continue;
             }
-        } else if self.active == 2{
-            for i in FIRST_BORDER_ROW + 1 .. MID_HEIGHT {
-                for j in MID_WIDTH + 1..WINDOWS_WIDTH{
-                    if self.screen[i][j] == ' ' && !last_char {
-                        spot = (i, j); 
-                        last_char = true;
-                    }
-                    if self.screen[i][j] != ' ' && last_char {
-                        last_char = false;
-                    }
-                }
-
+            let process = &self.windows[window - 1];
+            let (row, col) = if window == self.active {
+                file_spot(window, self.buffer_offset - self.file_scroll * self.file_columns(), self.file_columns())
+            } else {
+                file_spot(window, 0, self.file_columns())
+            };
+            for i in 0..MAX_FILENAME_BYTES + 1 {
+                self.display.plot(process.filename[i], col + i, row, ColorCode::new(Color::Black, Color::White));
             }
-        } else if self.active == 3{
-            for i in MID_HEIGHT + 1 .. LAST_BORDER_ROW {
-                for j in 1..MID_WIDTH{
-                    if self.screen[i][j] == ' ' && !last_char {
-                        spot = (i, j); 
-                        last_char = true;
-                    }
-                    if self.screen[i][j] != ' ' && last_char {
-                        last_char = false;
-                    }
-                }
+        }
+    }
 
+    // Highlights the selected job's row of the task manager.
+    fn draw_selected_job(&mut self) {
+        let job = match self.selected_job() {
+            Some(job) => job,
+            None => return,
+        };
+        let row = NUM_WINDOWS * TASK_ROWS + 1 + self.jobs[0..job].iter().filter(|job| job.launched).count();
+        if row < BUFFER_HEIGHT {
+            for col in WINDOWS_WIDTH + 1..BUFFER_WIDTH {
+                self.display.plot(self.screen[row][col], col, row, ColorCode::new(Color::Black, Color::White));
             }
-        } else if self.active == 4{
-            for i in MID_HEIGHT + 1 .. LAST_BORDER_ROW {
-                for j in MID_WIDTH + 1..WINDOWS_WIDTH{
-                    if self.screen[i][j] == ' ' && !last_char {
-                        spot = (i, j); 
-                        last_char = true;
-                    }
-                    if self.screen[i][j] != ' ' && last_char {
-                        last_char = false;
-                    }
-                }
+        }
+    }
 
-            }
+    // Fills in each window's block of the task manager (its priority, state,
+    // program and tick count), lists the background jobs underneath and draws
+    // it down the right of the screen. The job F11 selected is highlighted and
+    // its priority shown beside "Jobs".
+    pub fn draw_proc_status(&mut self) {
+        if self.fault.is_some() {
+            return;
+        }
+        self.process_info = [[' '; TASK_MANAGER_WIDTH]; BUFFER_HEIGHT];
+        for (i, process) in self.windows.iter().enumerate() {
+            let top = i * TASK_ROWS;
+            let _ = write!(RowWriter::new(&mut self.process_info[top][1..]), "F{} p{}", i + 1, self.scheduler.priority(i));
+            let _ = write!(RowWriter::new(&mut self.process_info[top + 1][1..]), "{}", process.state().name());
+            if process.launched {
+                let len = process.filename_len().min(TASK_MANAGER_WIDTH - 1);
+                self.process_info[top + 2][1..len + 1].copy_from_slice(&process.filename[0..len]);
+            }
+            let _ = write_ticks(&mut RowWriter::new(&mut self.process_info[top + 3][1..]), process.ticks);
         }
-        if self.new_line {
-            self.new_line = false;
-            spot.0 = spot.0 + 1;
-            if self.active == 1 || self.active == 3 {
-                spot.1 = 1;
+        let top = NUM_WINDOWS * TASK_ROWS;
+        let selected = self.selected_job();
+        let mut header = RowWriter::new(&mut self.process_info[top][1..]);
+        let _ = match selected {
+            Some(job) => write!(header, "Jobs p{}", self.scheduler.priority(NUM_WINDOWS + job)),
+            None => write!(header, "Jobs"),
+        };
+        for (row, job) in (top + 1..BUFFER_HEIGHT).zip(self.jobs.iter().filter(|job| job.launched)) {
+            let mut out = RowWriter::new(&mut self.process_info[row][1..]);
+            let _ = write!(out, "#{} ", job.pid);
+            if job.state() == ProcessState::Paused {
+                let _ = out.write_str(ProcessState::Paused.name());
             } else {
-                //println!("{:?}", spot);
-                //panic!();
-                spot.1 = MID_WIDTH + 1
+                for c in &job.filename[0..job.filename_len()] {
+                    let _ = out.write_char(*c);
+                }
             }
-           
         }
-        
-        for char in chars {
-            if *char == ('\n' as u8) {
-                if spot.0 + 1 == MID_HEIGHT {
-                    //Do something with scrolling?
-                } else  {
-                    self.new_line = true;
-                }
-            } else{
-                self.screen[spot.0][spot.1] = *char as char;
-                if spot.1 + 1 == MID_WIDTH {
-                    spot = (spot.0 + 1, 1)
-                } else {
-                    spot = (spot.0, spot.1 + 1)
-                }
 
+        for i in 0..BUFFER_HEIGHT {
+            for j in 1..TASK_MANAGER_WIDTH {
+                self.screen[i][WINDOWS_WIDTH + j] = self.process_info[i][j];
             }
+            for j in WINDOWS_WIDTH.. WINDOWS_WIDTH + TASK_MANAGER_WIDTH {
+                self.display.plot(self.screen[i][j], j, i, ColorCode::new(Color::White, Color::Black))
+            }
+        }
+        self.draw_selected_job();
+    }
+
+
+}
+
+impl Kernel<MemoryDisplay> {
+    // Where `text` was drawn inside `window`, as a row and column within it.
+    pub fn find_in_window(&self, window: usize, text: &str) -> Option<(usize, usize)> {
+        self.display.find(window_cols(window), window_rows(window), text)
+    }
+}
+
+pub struct KernelOutput<'a> {
+    place : Place,
+    screen : &'a mut [[char; BUFFER_WIDTH]; BUFFER_HEIGHT],
+    console : &'a mut Console,
+
+}
+
+impl <'a> KernelOutput<'a> {
+    fn new(place: Place, screen: &'a mut [[char; BUFFER_WIDTH]; BUFFER_HEIGHT], console: &'a mut Console) -> Self{
+        Self{place, screen, console}
+    }
+
+    // Screen position the next character goes at, moving the cursor to the
+    // next row when the current one is full and scrolling the window once it
+    // runs out of rows. Background jobs have no screen position.
+    fn next_spot(&mut self) -> Option<(usize, usize)> {
+        let window = match self.place {
+            Place::Window(window) => window,
+            Place::Job(_) => return None,
+        };
+        let rows = window_rows(window);
+        let cols = window_cols(window);
+        let console = &mut *self.console;
+        if console.new_line || console.cursor.1 == cols.len() {
+            console.new_line = false;
+            console.cursor = (console.cursor.0 + 1, 0);
+        }
+        if console.cursor.0 == rows.len() {
+            console.push_history(&self.screen[rows.start][cols.clone()]);
+            scroll_window(self.screen, window);
+            console.cursor.0 -= 1;
+        }
+        Some((rows.start + console.cursor.0, cols.start + console.cursor.1))
+    }
+
+    // Writes one character at the cursor.
+    fn put(&mut self, c: char) {
+        if let Some((row, col)) = self.next_spot() {
+            self.screen[row][col] = c;
+            self.console.cursor.1 += 1;
         }
+    }
+
+    // Whether nothing has been printed on the current line yet.
+    fn at_line_start(&self) -> bool {
+        self.console.log.last().is_none_or(|c| c == b'\n')
+    }
 
+    // Logs a character and, in a window, shows it.
+    fn show(&mut self, c: u8) {
+        self.console.log.push(c);
+        if c == b'\n' {
+            self.console.new_line = true;
+        } else {
+            self.put(c as char);
+        }
+    }
+
+    // Shows output logged earlier, without sending it to serial again.
+    fn replay(&mut self, log: &ProcessLog) {
+        for c in log.iter() {
+            self.show(c);
+        }
+    }
+
+    // Copies a character to the serial port, starting each line there with
+    // the window or job it came from.
+    fn mirror(&mut self, c: u8) {
+        if !self.console.serial_line {
+            serial_print!("[{}] ", self.place);
+            self.console.serial_line = true;
+        }
+        serial::send(c);
+        if c == b'\n' {
+            self.console.serial_line = false;
+        }
+    }
+}
+
+impl Write for KernelOutput<'_> {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        self.print(s.as_bytes());
+        Ok(())
+    }
+}
+
+impl InterpreterOutput for KernelOutput<'_> {
+    fn print(&mut self, chars: &[u8]) {
+        for char in chars {
+            self.mirror(*char);
+            self.show(*char);
+        }
     }
 }
 
@@ -1544,3 +2433,639 @@ fn highlight_color() -> ColorCode {
     ColorCode::new(Color::Black, Color::White)
 }
 
+fn fault_color() -> ColorCode {
+    ColorCode::new(Color::White, Color::Red)
+}
+
+
+// Host-only: these need std for threads and strings.
+#[cfg(all(test, not(target_os = "none")))]
+mod tests {
+    use super::*;
+    use display::MemoryDisplay;
+
+    type TestKernel = Kernel<MemoryDisplay>;
+
+    // The boot stack, from kernel-stack-size in Cargo.toml. Each test runs on
+    // a stack that size, so a kernel that overflows it fails here first.
+    const BOOT_STACK_BYTES: usize = 512 * 4096;
+
+    fn with_kernel(test: fn(&mut TestKernel)) {
+        std::thread::Builder::new()
+            .stack_size(BOOT_STACK_BYTES)
+            .spawn(move || {
+                let mut slot = Box::new_uninit();
+                let kernel = Kernel::init_in(&mut slot, MemoryDisplay::new());
+                kernel.draw();
+                test(kernel);
+            })
+            .unwrap()
+            .join()
+            .unwrap();
+    }
+
+    fn press(kernel: &mut TestKernel, key: KeyCode) {
+        kernel.key(DecodedKey::RawKey(key));
+    }
+
+    fn type_text(kernel: &mut TestKernel, text: &str) {
+        for c in text.chars() {
+            kernel.key(DecodedKey::Unicode(c));
+        }
+    }
+
+    // What is drawn inside a window, one line per row.
+    fn window_text(kernel: &TestKernel, window: usize) -> String {
+        window_rows(window)
+            .map(|row| window_cols(window).map(|col| kernel.display.char_at(col, row)).collect::<String>() + "\n")
+            .collect()
+    }
+
+    fn file_text(kernel: &mut TestKernel, name: &str) -> String {
+        let mut contents = [0; MAX_FILE_BYTES];
+        let len = kernel.read_file(name, &mut contents).unwrap();
+        String::from_utf8(contents[0..len].to_vec()).unwrap()
+    }
+
+    // Moves the active window's highlight right until it reaches `name`.
+    // Files made with F5 have their names padded with spaces.
+    fn select(kernel: &mut TestKernel, name: &str) {
+        while from_utf8(trim_filename(&kernel.windows[kernel.active - 1].filename_bytes())).map(str::trim_end) != Ok(name) {
+            press(kernel, KeyCode::ArrowRight);
+        }
+    }
+
+    fn program(name: &str) -> &'static str {
+        PROGRAMS.iter().find(|(filename, _)| *filename == name).unwrap().1
+    }
+
+    #[test]
+    fn starts_with_the_programs_listed_in_f1() {
+        with_kernel(|kernel| {
+            let text = window_text(kernel, 1);
+            for (name, _) in PROGRAMS {
+                assert!(text.contains(name), "{} missing from\n{}", name, text);
+            }
+            let (row, col) = file_spot(1, 0, FILE_COLUMNS);
+            assert_eq!(kernel.display.color_at(col, row), highlight_color());
+        });
+    }
+
+    #[test]
+    fn function_keys_move_the_active_border() {
+        with_kernel(|kernel| {
+            assert_eq!(kernel.display.char_at(1, FIRST_BORDER_ROW), '*');
+            press(kernel, KeyCode::F2);
+            assert_eq!(kernel.display.char_at(1, FIRST_BORDER_ROW), '.');
+            assert_eq!(kernel.display.char_at(MID_WIDTH + 1, FIRST_BORDER_ROW), '*');
+        });
+    }
+
+    #[test]
+    fn f7_and_f8_change_the_active_priority() {
+        with_kernel(|kernel| {
+            press(kernel, KeyCode::F2);
+            press(kernel, KeyCode::F8);
+            assert_eq!(kernel.display.char_at(WINDOWS_WIDTH + 5, TASK_ROWS), '4');
+            assert_eq!(kernel.display.char_at(WINDOWS_WIDTH + 5, 0), '3');
+            press(kernel, KeyCode::F7);
+            press(kernel, KeyCode::F7);
+            assert_eq!(kernel.scheduler.priority(1), 2);
+        });
+    }
+
+    #[test]
+    fn f9_pauses_and_resumes_a_program() {
+        with_kernel(|kernel| {
+            select(kernel, "hello");
+            type_text(kernel, "r");
+            press(kernel, KeyCode::F9);
+            for _ in 0..1000 {
+                kernel.run_one_instruction();
+            }
+            assert!(kernel.windows[0].running);
+            assert!(!window_text(kernel, 1).contains("[DONE]"));
+            let col = paused_col(1);
+            let header: String = kernel.display.row(FIRST_BORDER_ROW)[col..col + 8].iter().collect();
+            assert_eq!(header, "[PAUSED]");
+            press(kernel, KeyCode::F9);
+            for _ in 0..1000 {
+                kernel.run_one_instruction();
+            }
+            assert!(window_text(kernel, 1).contains("[DONE]"));
+        });
+    }
+
+    // What the task manager shows on `row`.
+    fn task_row(kernel: &TestKernel, row: usize) -> String {
+        kernel.display.row(row)[WINDOWS_WIDTH + 1..].iter().collect::<String>().trim_end().to_string()
+    }
+
+    #[test]
+    fn task_manager_shows_state_program_and_ticks() {
+        with_kernel(|kernel| {
+            kernel.draw_proc_status();
+            assert_eq!(task_row(kernel, 0), "F1 p3");
+            assert_eq!(task_row(kernel, 1), "idle");
+            select(kernel, "hello");
+            type_text(kernel, "r");
+            press(kernel, KeyCode::F9);
+            kernel.draw_proc_status();
+            assert_eq!(task_row(kernel, 1), "paused");
+            assert_eq!(task_row(kernel, 2), "hello");
+            press(kernel, KeyCode::F9);
+            for _ in 0..1000 {
+                kernel.run_one_instruction();
+            }
+            kernel.draw_proc_status();
+            assert_eq!(task_row(kernel, 1), "done");
+            assert_eq!(task_row(kernel, 3), kernel.windows[0].ticks.to_string());
+            assert_eq!(task_row(kernel, TASK_ROWS + 1), "idle");
+        });
+    }
+
+    #[test]
+    fn tick_counts_fit_in_five_characters() {
+        for (ticks, expected) in [(0, "0"), (9_999, "9999"), (10_000, "10k"), (9_999_999, "9999k"), (12_345_678, "12M")] {
+            let mut cells = [' '; 5];
+            write_ticks(&mut RowWriter::new(&mut cells), ticks).unwrap();
+            assert_eq!(cells.iter().collect::<String>().trim_end(), expected);
+        }
+    }
+
+    #[test]
+    fn arrows_move_the_highlight() {
+        with_kernel(|kernel| {
+            press(kernel, KeyCode::ArrowRight);
+            let (row, col) = file_spot(1, 0, FILE_COLUMNS);
+            assert_eq!(kernel.display.color_at(col, row), text_color());
+            let (row, col) = file_spot(1, 1, FILE_COLUMNS);
+            assert_eq!(kernel.display.color_at(col, row), highlight_color());
+        });
+    }
+
+    #[test]
+    fn f5_creates_a_file() {
+        with_kernel(|kernel| {
+            press(kernel, KeyCode::F5);
+            type_text(kernel, "notes\n");
+            press(kernel, KeyCode::F1);
+            assert!(window_text(kernel, 1).contains("notes"));
+        });
+    }
+
+    #[test]
+    fn f5_will_not_create_a_file_that_exists() {
+        with_kernel(|kernel| {
+            let (count, _) = kernel.files.list_directory().unwrap();
+            press(kernel, KeyCode::F5);
+            type_text(kernel, "hello\n");
+            assert_eq!(kernel.files.list_directory().unwrap().0, count);
+            let top: String = kernel.display.row(0).iter().collect();
+            assert_eq!(top.trim_end(), "hello already exists");
+        });
+    }
+
+    #[test]
+    fn prompts_leave_a_half_typed_f5_name_alone() {
+        with_kernel(|kernel| {
+            press(kernel, KeyCode::F5);
+            type_text(kernel, "ab");
+            press(kernel, KeyCode::F1);
+            type_text(kernel, "dn");
+            let top: String = kernel.display.row(0).iter().collect();
+            assert_eq!(top.trim_end(), format!("{}ab", FILENAME_PROMPT));
+        });
+    }
+
+    #[test]
+    fn f6_saves_the_edited_file() {
+        with_kernel(|kernel| {
+            press(kernel, KeyCode::ArrowRight);
+            let name = kernel.windows[0].filename_bytes();
+            let name = from_utf8(trim_filename(&name)).unwrap();
+            let original = program(name);
+            type_text(kernel, "e#");
+            press(kernel, KeyCode::End);
+            type_text(kernel, "   ");
+            press(kernel, KeyCode::F6);
+            assert!(!kernel.editing);
+            assert_eq!(file_text(kernel, name), format!("#{}", original));
+        });
+    }
+
+    #[test]
+    fn footer_shows_the_blocks_used_on_the_disk() {
+        with_kernel(|kernel| {
+            let used = kernel.blocks_used;
+            assert!(used > 0 && used < NUM_BLOCKS);
+            assert!(window_text(kernel, 1).contains(&format!("{}/{} blocks", used, NUM_BLOCKS)));
+            type_text(kernel, "e");
+            type_text(kernel, &"x".repeat(BLOCK_SIZE));
+            press(kernel, KeyCode::F6);
+            assert_eq!(kernel.blocks_used, used + 1);
+            assert!(window_text(kernel, 1).contains(&format!("{}/{} blocks", used + 1, NUM_BLOCKS)));
+        });
+    }
+
+    #[test]
+    fn switching_windows_does_not_measure_the_files_again() {
+        with_kernel(|kernel| {
+            assert!(!kernel.usage_stale);
+            kernel.blocks_used = 0;
+            press(kernel, KeyCode::F2);
+            type_text(kernel, "s");
+            assert!(window_text(kernel, 2).contains(&format!("0/{} blocks", NUM_BLOCKS)));
+        });
+    }
+
+    // A disk whose blocks all read back as `fill`, or fail to read if there
+    // is none.
+    struct FixedDisk {
+        fill: Option<u8>,
+    }
+
+    impl BlockDevice<BLOCK_SIZE> for FixedDisk {
+        type Error = ();
+
+        fn read_block(&mut self, _block: usize, buffer: &mut [u8; BLOCK_SIZE]) -> Result<(), ()> {
+            *buffer = [self.fill.ok_or(())?; BLOCK_SIZE];
+            Ok(())
+        }
+
+        fn write_block(&mut self, _block: usize, _buffer: &[u8; BLOCK_SIZE]) -> Result<(), ()> {
+            Err(())
+        }
+    }
+
+    #[test]
+    fn only_a_disk_without_the_magic_counts_as_unformatted() {
+        with_kernel(|kernel| {
+            assert_eq!(load_files(&mut FixedDisk {fill: Some(0)}, &mut kernel.files).ok(), Some(false));
+            assert!(load_files(&mut FixedDisk {fill: None}, &mut kernel.files).is_err());
+        });
+    }
+
+    #[test]
+    fn d_then_y_deletes_the_highlighted_file() {
+        with_kernel(|kernel| {
+            let (first, _) = PROGRAMS[0];
+            type_text(kernel, "d");
+            assert!(kernel.display.row(0).iter().collect::<String>().starts_with("Delete"));
+            type_text(kernel, "y");
+            assert!(!kernel.file_exists(first.as_bytes()).unwrap());
+            assert!(!window_text(kernel, 1).contains(first));
+        });
+    }
+
+    #[test]
+    fn n_renames_the_highlighted_file() {
+        with_kernel(|kernel| {
+            let (first, contents) = PROGRAMS[0];
+            type_text(kernel, "nfirst\n");
+            assert!(!kernel.file_exists(first.as_bytes()).unwrap());
+            assert_eq!(file_text(kernel, "first"), contents);
+            assert!(window_text(kernel, 1).contains("first"));
+        });
+    }
+
+    #[test]
+    fn backspace_stops_at_the_start_of_a_prompt() {
+        with_kernel(|kernel| {
+            select(kernel, "hello");
+            type_text(kernel, "ba1\u{08}\u{08}");
+            let top: String = kernel.display.row(0).iter().collect();
+            assert_eq!(top.trim_end(), "Attach job #");
+        });
+    }
+
+    #[test]
+    fn a_attaches_to_the_window_it_was_pressed_in() {
+        with_kernel(|kernel| {
+            write_program(kernel, "echo", ECHO);
+            press(kernel, KeyCode::F2);
+            select(kernel, "echo");
+            type_text(kernel, "r");
+            press(kernel, KeyCode::F1);
+            select(kernel, "hello");
+            type_text(kernel, "ba");
+            press(kernel, KeyCode::F5);
+            press(kernel, KeyCode::F2);
+            type_text(kernel, "2\n");
+            assert!(kernel.windows[1].running);
+            assert_eq!(kernel.windows[0].pid, 2);
+            assert!(!kernel.jobs.iter().any(|job| job.launched));
+        });
+    }
+
+    #[test]
+    fn a_will_not_attach_over_a_busy_window() {
+        with_kernel(|kernel| {
+            select(kernel, "hello");
+            type_text(kernel, "ba");
+            kernel.windows[0].shell = true;
+            type_text(kernel, "1\n");
+            assert!(kernel.jobs[0].launched);
+            let top: String = kernel.display.row(0).iter().collect();
+            assert_eq!(top.trim_end(), "F1 is busy");
+        });
+    }
+
+    #[test]
+    fn running_hello_prints_then_finishes() {
+        with_kernel(|kernel| {
+            select(kernel, "hello");
+            type_text(kernel, "r");
+            for _ in 0..1000 {
+                kernel.run_one_instruction();
+            }
+            let text = window_text(kernel, 1);
+            assert!(text.contains("Hello, world!"), "{}", text);
+            assert!(text.contains("[DONE]"), "{}", text);
+            assert!(!kernel.windows[0].running);
+        });
+    }
+
+    // Saves `program` as a new file called `name`, leaving F1 active.
+    fn write_program(kernel: &mut TestKernel, name: &str, program: &str) {
+        press(kernel, KeyCode::F5);
+        type_text(kernel, &format!("{}\n", name));
+        press(kernel, KeyCode::F1);
+        select(kernel, name);
+        type_text(kernel, "e");
+        type_text(kernel, program);
+        press(kernel, KeyCode::F6);
+    }
+
+    fn run_to_input(kernel: &mut TestKernel) {
+        for _ in 0..1000 {
+            if kernel.windows[0].waiting {
+                return;
+            }
+            kernel.run_one_instruction();
+        }
+        panic!("the program never asked for input");
+    }
+
+    fn run_echo(kernel: &mut TestKernel) {
+        select(kernel, "echo");
+        type_text(kernel, "r");
+        run_to_input(kernel);
+    }
+
+    const ECHO: &str = "x := input(\"name\")\nprint(x)";
+
+    #[test]
+    fn input_can_be_edited_before_it_is_entered() {
+        with_kernel(|kernel| {
+            write_program(kernel, "echo", ECHO);
+            run_echo(kernel);
+            type_text(kernel, "abd\u{08}");
+            press(kernel, KeyCode::ArrowLeft);
+            type_text(kernel, "c");
+            assert!(window_text(kernel, 1).contains("nameacb"));
+            type_text(kernel, "\n");
+            for _ in 0..1000 {
+                kernel.run_one_instruction();
+            }
+            let text = window_text(kernel, 1);
+            assert!(text.lines().any(|line| line.trim_end() == "acb"), "{}", text);
+        });
+    }
+
+    #[test]
+    fn up_recalls_earlier_input_in_the_same_window() {
+        with_kernel(|kernel| {
+            write_program(kernel, "echo", ECHO);
+            run_echo(kernel);
+            type_text(kernel, "first\n");
+            for _ in 0..1000 {
+                kernel.run_one_instruction();
+            }
+            type_text(kernel, "r");
+            run_to_input(kernel);
+            press(kernel, KeyCode::ArrowUp);
+            assert!(window_text(kernel, 1).contains("namefirst"));
+            type_text(kernel, "\n");
+            for _ in 0..1000 {
+                kernel.run_one_instruction();
+            }
+            let text = window_text(kernel, 1);
+            assert!(text.lines().any(|line| line.trim_end() == "first"), "{}", text);
+        });
+    }
+
+    #[test]
+    fn lines_typed_ahead_answer_later_prompts_in_order() {
+        with_kernel(|kernel| {
+            write_program(kernel, "two", "x := input(\"a\")\nprint(x)\ny := input(\"b\")\nprint(y)");
+            select(kernel, "two");
+            type_text(kernel, "r");
+            type_text(kernel, "one\ntwo\n");
+            let header: String = kernel.display.row(FIRST_BORDER_ROW)[WINDOW_WIDTH / 2 + 3..WINDOW_WIDTH / 2 + 7].iter().collect();
+            assert_eq!(header, "[+2]");
+            for _ in 0..1000 {
+                kernel.run_one_instruction();
+            }
+            let text = window_text(kernel, 1);
+            let lines: Vec<&str> = text.lines().map(str::trim_end).filter(|line| !line.is_empty()).collect();
+            assert_eq!(lines, ["aone", "one", "btwo", "two", "[DONE]"]);
+        });
+    }
+
+    #[test]
+    fn background_jobs_run_off_screen_until_attached() {
+        with_kernel(|kernel| {
+            select(kernel, "hello");
+            type_text(kernel, "b");
+            assert!(kernel.jobs[0].launched);
+            assert!(!kernel.windows[0].launched);
+            for _ in 0..1000 {
+                kernel.run_one_instruction();
+            }
+            assert!(!kernel.jobs[0].running);
+            assert!(!window_text(kernel, 1).contains("Hello"));
+            kernel.draw_proc_status();
+            assert_eq!(task_row(kernel, NUM_WINDOWS * TASK_ROWS), "Jobs");
+            assert_eq!(task_row(kernel, NUM_WINDOWS * TASK_ROWS + 1), "#1 hello");
+            type_text(kernel, "a1\n");
+            let text = window_text(kernel, 1);
+            assert!(text.contains("Hello, world!") && text.contains("[DONE]"), "{}", text);
+            assert!(!kernel.jobs[0].launched);
+        });
+    }
+
+    #[test]
+    fn f11_picks_a_background_job_for_f7_to_f9() {
+        with_kernel(|kernel| {
+            write_program(kernel, "echo", ECHO);
+            run_echo(kernel);
+            press(kernel, KeyCode::F10);
+            let jobs = NUM_WINDOWS * TASK_ROWS;
+            press(kernel, KeyCode::F11);
+            assert_eq!(task_row(kernel, jobs), "Jobs p3");
+            assert_eq!(task_row(kernel, jobs + 1), "#1 echo");
+            assert_eq!(kernel.display.color_at(WINDOWS_WIDTH + 1, jobs + 1), ColorCode::new(Color::Black, Color::White));
+            press(kernel, KeyCode::F8);
+            assert_eq!(kernel.scheduler.priority(NUM_WINDOWS), 4);
+            assert_eq!(kernel.scheduler.priority(0), 3);
+            press(kernel, KeyCode::F9);
+            assert!(kernel.jobs[0].paused);
+            assert_eq!(task_row(kernel, jobs + 1), "#1 paused");
+            press(kernel, KeyCode::F11);
+            assert_eq!(task_row(kernel, jobs), "Jobs");
+            assert_eq!(kernel.display.color_at(WINDOWS_WIDTH + 1, jobs + 1), ColorCode::new(Color::White, Color::Black));
+            press(kernel, KeyCode::F9);
+            assert!(kernel.jobs[0].paused);
+        });
+    }
+
+    #[test]
+    fn f10_detaches_a_program_that_can_be_answered_once_attached() {
+        with_kernel(|kernel| {
+            write_program(kernel, "echo", ECHO);
+            run_echo(kernel);
+            press(kernel, KeyCode::F10);
+            assert!(!kernel.windows[0].launched);
+            assert!(kernel.jobs[0].waiting);
+            assert!(window_text(kernel, 1).contains("hello"));
+            type_text(kernel, "a1\n");
+            assert!(window_text(kernel, 1).contains("name"));
+            type_text(kernel, "x\n");
+            for _ in 0..1000 {
+                kernel.run_one_instruction();
+            }
+            let text = window_text(kernel, 1);
+            assert!(text.lines().any(|line| line.trim_end() == "x"), "{}", text);
+        });
+    }
+
+    // Types a command into the shell in F1 and returns what F1 shows after.
+    fn shell(kernel: &mut TestKernel, command: &str) -> String {
+        type_text(kernel, &format!("{}\n", command));
+        window_text(kernel, 1)
+    }
+
+    #[test]
+    fn c_opens_a_shell_that_lists_and_shows_files() {
+        with_kernel(|kernel| {
+            type_text(kernel, "c");
+            assert!(kernel.windows[0].shell);
+            assert!(window_text(kernel, 1).contains("$ "));
+            let text = shell(kernel, "ls");
+            assert!(text.contains("hello") && text.contains(&format!("{} B", program("hello").len())), "{}", text);
+            let text = shell(kernel, "cat nums");
+            assert!(text.contains("print(257)"), "{}", text);
+            let text = shell(kernel, "help");
+            assert!(text.contains("kill PID"), "{}", text);
+            let text = shell(kernel, "cat");
+            assert!(text.contains("usage: cat FILE"), "{}", text);
+            let text = shell(kernel, "exit");
+            assert!(!kernel.windows[0].shell);
+            assert!(text.contains("countdown"), "{}", text);
+        });
+    }
+
+    #[test]
+    fn shell_commands_copy_rename_and_delete_files() {
+        with_kernel(|kernel| {
+            type_text(kernel, "c");
+            shell(kernel, "cp hello greet");
+            assert_eq!(file_text(kernel, "greet"), program("hello"));
+            assert!(shell(kernel, "cp hello nums").contains("nums: file exists"));
+            shell(kernel, "mv greet hi");
+            assert!(kernel.file_exists(b"hi").unwrap() && !kernel.file_exists(b"greet").unwrap());
+            shell(kernel, "rm hi");
+            assert!(!kernel.file_exists(b"hi").unwrap());
+            assert!(shell(kernel, "cat hi").contains("hi: no such file"));
+        });
+    }
+
+    #[test]
+    fn shell_runs_lists_and_kills_programs() {
+        with_kernel(|kernel| {
+            write_program(kernel, "echo", ECHO);
+            type_text(kernel, "c");
+            assert!(shell(kernel, "run hello 2").contains("#1 in F2"));
+            assert!(shell(kernel, "run pi 1").contains("F1 is busy"));
+            for _ in 0..1000 {
+                kernel.run_one_instruction();
+            }
+            assert!(window_text(kernel, 2).contains("Hello, world!"));
+            assert!(shell(kernel, "run echo").contains("#2 in the background"));
+            for _ in 0..1000 {
+                kernel.run_one_instruction();
+            }
+            let text = shell(kernel, "ps");
+            assert!(text.lines().any(|line| line.starts_with("2    bg    waiting  echo")), "{}", text);
+            assert!(shell(kernel, "kill 2").contains("killed #2"));
+            assert!(!kernel.jobs[0].launched);
+            shell(kernel, "kill 1");
+            assert!(window_text(kernel, 2).contains("countdown"));
+        });
+    }
+
+    // Faults the way the kernel can: by reading a file that isn't there.
+    fn fault(kernel: &mut TestKernel) {
+        let mut contents = [0; MAX_FILE_BYTES];
+        let result = kernel.read_file("missing", &mut contents).map(|_| ());
+        kernel.catch(result);
+        kernel.draw();
+    }
+
+    fn screen_text(kernel: &TestKernel) -> String {
+        (0..BUFFER_HEIGHT).map(|row| kernel.display.row(row).iter().collect::<String>() + "\n").collect()
+    }
+
+    #[test]
+    fn a_fault_reports_where_it_happened_and_what_was_running() {
+        with_kernel(|kernel| {
+            select(kernel, "hello");
+            type_text(kernel, "r");
+            kernel.run_one_instruction();
+            let ticks = kernel.windows[0].ticks;
+            fault(kernel);
+            let text = screen_text(kernel);
+            assert!(text.contains("KERNEL FAULT"), "{}", text);
+            assert!(text.contains("opening a file: FileNotFound"), "{}", text);
+            assert!(text.contains("src/lib.rs:"), "{}", text);
+            assert!(text.contains("Active window: F1"), "{}", text);
+            let line = text.lines().find(|line| line.trim_start().starts_with("F1")).unwrap();
+            assert!(line.contains("hello") && line.contains(&format!("running   {}", ticks)), "{}", line);
+            assert_eq!(kernel.display.color_at(0, 0), fault_color());
+            for _ in 0..100 {
+                kernel.run_one_instruction();
+            }
+            assert_eq!(kernel.windows[0].ticks, ticks);
+        });
+    }
+
+    #[test]
+    fn r_on_the_fault_screen_asks_for_a_restart() {
+        with_kernel(|kernel| {
+            fault(kernel);
+            press(kernel, KeyCode::F2);
+            type_text(kernel, "e");
+            assert_eq!(kernel.active, 1);
+            assert!(!kernel.wants_restart());
+            type_text(kernel, "r");
+            assert!(kernel.wants_restart());
+        });
+    }
+
+    #[test]
+    fn restarting_starts_over_in_place() {
+        with_kernel(|kernel| {
+            select(kernel, "hello");
+            type_text(kernel, "r");
+            fault(kernel);
+            type_text(kernel, "r");
+            kernel.restart();
+            kernel.draw();
+            assert!(!kernel.wants_restart());
+            assert!(kernel.fault.is_none());
+            assert!(!kernel.windows[0].launched);
+            assert!(window_text(kernel, 1).contains("hello"));
+        });
+    }
+}
@@ -1,51 +1,12 @@
-#![no_std]
-#![no_main]
-
-use pc_keyboard::DecodedKey;
-use pluggable_interrupt_os::HandlerTable;
-use pluggable_interrupt_os::vga_buffer::clear_screen;
-use swim_template::Kernel;
-use crossbeam::atomic::AtomicCell;
+#![cfg_attr(target_os = "none", no_std)]
+#![cfg_attr(target_os = "none", no_main)]
 
+#[cfg(target_os = "none")]
 #[no_mangle]
 pub extern "C" fn _start() -> ! {
-    HandlerTable::new()
-        .keyboard(key)
-        .timer(tick)
-        .startup(startup)
-        .cpu_loop(cpu_loop)
-        .start()
+    swim_template::runtime::start()
 }
 
-static LAST_KEY: AtomicCell<Option<DecodedKey>> = AtomicCell::new(None);
-static TICKS: AtomicCell<usize> = AtomicCell::new(0);
-
-fn cpu_loop() -> ! {
-    let mut kernel = Kernel::new();
-    let mut last_tick = 0;
-    kernel.draw();
-    loop {
-        if let Some(key) = LAST_KEY.load() {
-            LAST_KEY.store(None);
-            kernel.key(key);
-        }
-        let current_tick = TICKS.load();
-        if current_tick > last_tick {
-            last_tick = current_tick;
-            kernel.draw_proc_status();
-        }
-        kernel.run_one_instruction();
-    }
-}
-
-fn tick() {
-    TICKS.fetch_add(1);
-}
-
-fn key(key: DecodedKey) {
-    LAST_KEY.swap(Some(key));
-}
-
-fn startup() {
-    clear_screen();
-}
+// The kernel only boots bare-metal; host builds exist for `cargo test`.
+#[cfg(not(target_os = "none"))]
+fn main() {}
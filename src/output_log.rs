@@ -0,0 +1,49 @@
+// The last BYTES bytes a process has printed, so that its output can be shown
+// again when it moves into a window after running in the background.
+#[derive(Clone, Copy)]
+pub struct OutputLog<const BYTES: usize> {
+    bytes: [u8; BYTES],
+    start: usize,
+    len: usize,
+}
+
+impl<const BYTES: usize> OutputLog<BYTES> {
+    pub fn new() -> Self {
+        Self {bytes: [0; BYTES], start: 0, len: 0}
+    }
+
+    // Adds a byte, dropping the oldest one once the log is full.
+    pub fn push(&mut self, byte: u8) {
+        if self.len < BYTES {
+            self.bytes[(self.start + self.len) % BYTES] = byte;
+            self.len += 1;
+        } else {
+            self.bytes[self.start] = byte;
+            self.start = (self.start + 1) % BYTES;
+        }
+    }
+
+    pub fn last(&self) -> Option<u8> {
+        self.len.checked_sub(1).map(|i| self.bytes[(self.start + i) % BYTES])
+    }
+
+    // The bytes kept, oldest first.
+    pub fn iter(&self) -> impl Iterator<Item = u8> + '_ {
+        (0..self.len).map(move |i| self.bytes[(self.start + i) % BYTES])
+    }
+}
+
+#[cfg(all(test, not(target_os = "none")))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_full_log_keeps_the_newest_bytes() {
+        let mut log = OutputLog::<4>::new();
+        for byte in b"abcdef" {
+            log.push(*byte);
+        }
+        assert_eq!(log.iter().collect::<Vec<u8>>(), b"cdef");
+        assert_eq!(log.last(), Some(b'f'));
+    }
+}
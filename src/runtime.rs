@@ -0,0 +1,117 @@
+use core::arch::asm;
+use core::mem::MaybeUninit;
+use core::panic::PanicInfo;
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use crossbeam::atomic::AtomicCell;
+use pc_keyboard::DecodedKey;
+use spin::Mutex;
+
+use crate::display::Vga;
+use crate::fault::Fault;
+use crate::{interrupts, serial, testing, Kernel};
+
+// Runs the kernel once the bootloader hands over. The kernel is built in a
+// static slot rather than on the boot stack, and the interrupt handlers pass
+// it keys and timer ticks through LAST_KEY and TICKS.
+//
+// The panic handler puts the kernel on the fault screen and then goes back to
+// serving it from the top of the boot stack, so every fault starts from the
+// same stack depth however many there have been.
+
+static KERNEL: Mutex<MaybeUninit<Kernel<Vga>>> = Mutex::new(MaybeUninit::uninit());
+static STARTED: AtomicBool = AtomicBool::new(false);
+static PANICKING: AtomicBool = AtomicBool::new(false);
+// Where `serve` starts on the boot stack.
+static STACK_TOP: AtomicU64 = AtomicU64::new(0);
+
+static LAST_KEY: AtomicCell<Option<DecodedKey>> = AtomicCell::new(None);
+static TICKS: AtomicCell<usize> = AtomicCell::new(0);
+
+pub fn start() -> ! {
+    let top: u64;
+    unsafe { asm!("mov {}, rsp", out(reg) top) };
+    // The System V ABI wants the stack 16-byte aligned at each call.
+    STACK_TOP.store(top & !0xf, Ordering::SeqCst);
+    interrupts::init();
+    let mut slot = KERNEL.lock();
+    let kernel = Kernel::init_with_disk(&mut slot, Vga, crate::open_disk());
+    STARTED.store(true, Ordering::SeqCst);
+    serve(kernel)
+}
+
+pub fn key(key: DecodedKey) {
+    LAST_KEY.store(Some(key));
+}
+
+pub fn tick() {
+    TICKS.fetch_add(1);
+}
+
+fn serve(kernel: &mut Kernel<Vga>) -> ! {
+    let mut last_tick = TICKS.load();
+    kernel.draw();
+    loop {
+        if kernel.wants_restart() {
+            kernel.restart();
+            kernel.draw();
+        }
+        if let Some(key) = LAST_KEY.take() {
+            kernel.key(key);
+        }
+        if let Some(key) = serial::receive_key() {
+            kernel.key(key);
+        }
+        let current_tick = TICKS.load();
+        if current_tick > last_tick {
+            last_tick = current_tick;
+            kernel.draw_proc_status();
+        }
+        kernel.run_one_instruction();
+    }
+}
+
+// Called on the reset stack, which nothing below it is using any more.
+extern "C" fn resume() -> ! {
+    // The lock was taken by whatever was running when the panic hit.
+    unsafe { KERNEL.force_unlock() };
+    let mut slot = KERNEL.lock();
+    let kernel = unsafe { slot.assume_init_mut() };
+    interrupts::acknowledge_all();
+    PANICKING.store(false, Ordering::SeqCst);
+    x86_64::instructions::interrupts::enable();
+    serve(kernel)
+}
+
+fn halt() -> ! {
+    loop {
+        x86_64::instructions::hlt();
+    }
+}
+
+// Tests fail on a panic. Otherwise the panic goes on the fault screen, unless
+// it came before the kernel was built or while a fault was being shown, in
+// which case it is only printed.
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    x86_64::instructions::interrupts::disable();
+    if testing::running() {
+        serial_println!("[failed]\n{}", info);
+        testing::exit_qemu(testing::QemuExitCode::Failed);
+    }
+    if !STARTED.load(Ordering::SeqCst) || PANICKING.swap(true, Ordering::SeqCst) {
+        serial_println!("[kernel] {}; halting", info);
+        halt();
+    }
+    let fault = match info.location() {
+        Some(location) => Fault::at(location, format_args!("panic: {}", info.message())),
+        None => Fault::new(format_args!("panic: {}", info.message())),
+    };
+    unsafe { KERNEL.force_unlock() };
+    {
+        let mut slot = KERNEL.lock();
+        unsafe { slot.assume_init_mut() }.fail(fault);
+    }
+    unsafe {
+        asm!("mov rsp, {top}", "call {resume}", top = in(reg) STACK_TOP.load(Ordering::SeqCst), resume = sym resume, options(noreturn));
+    }
+}
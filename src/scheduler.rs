@@ -0,0 +1,129 @@
+pub const MIN_PRIORITY: usize = 1;
+pub const MAX_PRIORITY: usize = 5;
+pub const DEFAULT_PRIORITY: usize = 3;
+
+// Weighted round-robin over N processes. The process whose turn it is keeps
+// the CPU for `quantum` ticks per level of priority, then the turn passes to
+// the next process that can run. Processes that can't run (finished, waiting
+// for input) are passed over without using up a turn.
+pub struct Scheduler<const N: usize> {
+    current: Option<usize>,
+    used: usize,
+    quantum: usize,
+    priorities: [usize; N],
+}
+
+impl<const N: usize> Scheduler<N> {
+    pub fn new(quantum: usize) -> Self {
+        Self {current: None, used: 0, quantum: quantum.max(1), priorities: [DEFAULT_PRIORITY; N]}
+    }
+
+    pub fn set_quantum(&mut self, quantum: usize) {
+        self.quantum = quantum.max(1);
+    }
+
+    pub fn priority(&self, process: usize) -> usize {
+        self.priorities[process]
+    }
+
+    pub fn set_priority(&mut self, process: usize, priority: usize) {
+        self.priorities[process] = priority.clamp(MIN_PRIORITY, MAX_PRIORITY);
+    }
+
+    // Exchanges the priorities of two processes, for when they trade places.
+    // Whichever one has the turn keeps it under its new index.
+    pub fn swap(&mut self, a: usize, b: usize) {
+        self.priorities.swap(a, b);
+        if self.current == Some(a) {
+            self.current = Some(b);
+        } else if self.current == Some(b) {
+            self.current = Some(a);
+        }
+    }
+
+    // Index of the process to tick next, given which ones can run, or None if
+    // none can.
+    pub fn next(&mut self, runnable: [bool; N]) -> Option<usize> {
+        if let Some(current) = self.current {
+            if runnable[current] && self.used < self.quantum * self.priorities[current] {
+                self.used += 1;
+                return Some(current);
+            }
+        }
+        let start = self.current.map_or(0, |current| current + 1);
+        self.current = (0..N).map(|offset| (start + offset) % N).find(|i| runnable[*i]);
+        self.used = 1;
+        self.current
+    }
+}
+
+#[cfg(all(test, not(target_os = "none")))]
+mod tests {
+    use super::*;
+
+    fn turns<const N: usize>(scheduler: &mut Scheduler<N>, runnable: [bool; N], count: usize) -> Vec<Option<usize>> {
+        (0..count).map(|_| scheduler.next(runnable)).collect()
+    }
+
+    fn flat<const N: usize>(quantum: usize) -> Scheduler<N> {
+        let mut scheduler = Scheduler::new(quantum);
+        for i in 0..N {
+            scheduler.set_priority(i, MIN_PRIORITY);
+        }
+        scheduler
+    }
+
+    #[test]
+    fn each_process_gets_a_full_quantum_in_order() {
+        let mut scheduler = flat::<3>(2);
+        assert_eq!(turns(&mut scheduler, [true; 3], 7), [Some(0), Some(0), Some(1), Some(1), Some(2), Some(2), Some(0)]);
+    }
+
+    #[test]
+    fn blocked_processes_are_skipped() {
+        let mut scheduler = flat::<4>(1);
+        assert_eq!(turns(&mut scheduler, [false, true, false, true], 4), [Some(1), Some(3), Some(1), Some(3)]);
+    }
+
+    #[test]
+    fn a_process_that_blocks_gives_up_the_rest_of_its_turn() {
+        let mut scheduler = flat::<2>(3);
+        assert_eq!(scheduler.next([true, true]), Some(0));
+        assert_eq!(scheduler.next([false, true]), Some(1));
+        assert_eq!(scheduler.next([true, true]), Some(1));
+    }
+
+    #[test]
+    fn nothing_runs_when_nothing_is_runnable() {
+        let mut scheduler = Scheduler::<2>::new(3);
+        assert_eq!(scheduler.next([false, false]), None);
+        assert_eq!(scheduler.next([false, true]), Some(1));
+    }
+
+    #[test]
+    fn priority_multiplies_the_quantum() {
+        let mut scheduler = flat::<2>(1);
+        scheduler.set_priority(0, 3);
+        assert_eq!(turns(&mut scheduler, [true; 2], 5), [Some(0), Some(0), Some(0), Some(1), Some(0)]);
+    }
+
+    #[test]
+    fn swapped_processes_keep_their_priority_and_turn() {
+        let mut scheduler = flat::<3>(1);
+        scheduler.set_priority(0, 2);
+        assert_eq!(scheduler.next([true; 3]), Some(0));
+        scheduler.swap(0, 2);
+        assert_eq!(scheduler.priority(2), 2);
+        assert_eq!(scheduler.priority(0), MIN_PRIORITY);
+        assert_eq!(turns(&mut scheduler, [true; 3], 2), [Some(2), Some(0)]);
+    }
+
+    #[test]
+    fn priorities_stay_in_range() {
+        let mut scheduler = Scheduler::<1>::new(1);
+        scheduler.set_priority(0, 0);
+        assert_eq!(scheduler.priority(0), MIN_PRIORITY);
+        scheduler.set_priority(0, MAX_PRIORITY + 1);
+        assert_eq!(scheduler.priority(0), MAX_PRIORITY);
+    }
+}
@@ -0,0 +1,184 @@
+use core::fmt;
+use pc_keyboard::{DecodedKey, KeyCode};
+use uart_16550::SerialPort;
+#[cfg(target_os = "none")]
+use lazy_static::lazy_static;
+#[cfg(target_os = "none")]
+use spin::Mutex;
+#[cfg(target_os = "none")]
+use x86_64::instructions::{interrupts, port::Port};
+
+// COM1, which QEMU connects to stdio under `-nographic` or `-serial stdio`.
+#[cfg(target_os = "none")]
+const COM1: u16 = 0x3F8;
+#[cfg(target_os = "none")]
+const LINE_STATUS: u16 = COM1 + 5;
+#[cfg(target_os = "none")]
+const DATA_READY: u8 = 0x01;
+
+const ESC: u8 = 0x1b;
+// Terminals send a whole escape sequence at once, so when nothing follows ESC
+// within this many checks of the line status, ESC was pressed on its own.
+const ESCAPE_POLLS: usize = 100_000;
+
+#[cfg(target_os = "none")]
+lazy_static! {
+    static ref SERIAL1: Mutex<SerialPort> = {
+        let mut port = unsafe { SerialPort::new(COM1) };
+        port.init();
+        Mutex::new(port)
+    };
+}
+
+#[macro_export]
+macro_rules! serial_print {
+    ($($arg:tt)*) => ($crate::serial::_print(format_args!($($arg)*)));
+}
+
+#[macro_export]
+macro_rules! serial_println {
+    () => ($crate::serial_print!("\n"));
+    ($($arg:tt)*) => ($crate::serial_print!("{}\n", format_args!($($arg)*)));
+}
+
+// Runs `f` on COM1 with interrupts off, so the keyboard and timer handlers
+// can't interleave with it.
+#[cfg(target_os = "none")]
+fn with_port<R>(f: impl FnOnce(&mut SerialPort) -> R) -> Option<R> {
+    Some(interrupts::without_interrupts(|| f(&mut SERIAL1.lock())))
+}
+
+// Host builds, like the unit tests, have no serial port.
+#[cfg(not(target_os = "none"))]
+fn with_port<R>(_f: impl FnOnce(&mut SerialPort) -> R) -> Option<R> {
+    None
+}
+
+#[doc(hidden)]
+pub fn _print(args: fmt::Arguments) {
+    use core::fmt::Write;
+    with_port(|port| port.write_fmt(args).unwrap());
+}
+
+// Lets anything that writes to a `fmt::Write` write to COM1.
+pub struct SerialWriter;
+
+impl fmt::Write for SerialWriter {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        _print(format_args!("{}", s));
+        Ok(())
+    }
+}
+
+pub fn send(byte: u8) {
+    with_port(|port| port.send(byte));
+}
+
+// Whether COM1 has a byte waiting, checking up to `polls` times.
+#[cfg(target_os = "none")]
+fn data_ready(polls: usize) -> bool {
+    (0..polls).any(|_| unsafe { Port::<u8>::new(LINE_STATUS).read() } & DATA_READY != 0)
+}
+
+#[cfg(not(target_os = "none"))]
+fn data_ready(_polls: usize) -> bool {
+    true
+}
+
+// The next key received on COM1, if one has arrived. Terminals send carriage
+// return for Enter, DEL for backspace and escape sequences for arrows and
+// function keys.
+pub fn receive_key() -> Option<DecodedKey> {
+    with_port(|port| {
+        if !data_ready(1) {
+            return None;
+        }
+        let key = match port.receive() {
+            ESC => return Some(decode_escape(|| if data_ready(ESCAPE_POLLS) {Some(port.receive())} else {None})),
+            b'\r' => '\n',
+            0x7F => '\u{08}',
+            byte => byte as char,
+        };
+        Some(DecodedKey::Unicode(key))
+    }).flatten()
+}
+
+// Decodes the rest of a VT100/xterm escape sequence, read through `next`,
+// into the key it stands for: ESC [ A-D and H/F for the arrows, Home and End,
+// ESC O P-S for F1-F4 and ESC [ n ~ for the others. ESC on its own, or a
+// sequence not listed here, comes through as ESC.
+fn decode_escape(mut next: impl FnMut() -> Option<u8>) -> DecodedKey {
+    let esc = DecodedKey::Unicode(ESC as char);
+    let code = match next() {
+        Some(b'O') => match next() {
+            Some(b'P') => KeyCode::F1,
+            Some(b'Q') => KeyCode::F2,
+            Some(b'R') => KeyCode::F3,
+            Some(b'S') => KeyCode::F4,
+            _ => return esc,
+        },
+        Some(b'[') => {
+            let mut number: usize = 0;
+            loop {
+                match next() {
+                    Some(digit @ b'0'..=b'9') => number = number.saturating_mul(10).saturating_add((digit - b'0') as usize),
+                    Some(b'A') => break KeyCode::ArrowUp,
+                    Some(b'B') => break KeyCode::ArrowDown,
+                    Some(b'C') => break KeyCode::ArrowRight,
+                    Some(b'D') => break KeyCode::ArrowLeft,
+                    Some(b'H') => break KeyCode::Home,
+                    Some(b'F') => break KeyCode::End,
+                    Some(b'~') => match number {
+                        1 | 7 => break KeyCode::Home,
+                        4 | 8 => break KeyCode::End,
+                        5 => break KeyCode::PageUp,
+                        6 => break KeyCode::PageDown,
+                        11 => break KeyCode::F1,
+                        12 => break KeyCode::F2,
+                        13 => break KeyCode::F3,
+                        14 => break KeyCode::F4,
+                        15 => break KeyCode::F5,
+                        17 => break KeyCode::F6,
+                        18 => break KeyCode::F7,
+                        19 => break KeyCode::F8,
+                        20 => break KeyCode::F9,
+                        21 => break KeyCode::F10,
+                        23 => break KeyCode::F11,
+                        24 => break KeyCode::F12,
+                        _ => return esc,
+                    },
+                    _ => return esc,
+                }
+            }
+        }
+        _ => return esc,
+    };
+    DecodedKey::RawKey(code)
+}
+
+#[cfg(all(test, not(target_os = "none")))]
+mod tests {
+    use super::*;
+
+    fn decode(sequence: &[u8]) -> DecodedKey {
+        let mut bytes = sequence.iter().copied();
+        decode_escape(|| bytes.next())
+    }
+
+    #[test]
+    fn arrows_and_function_keys_become_raw_keys() {
+        assert_eq!(decode(b"[A"), DecodedKey::RawKey(KeyCode::ArrowUp));
+        assert_eq!(decode(b"[D"), DecodedKey::RawKey(KeyCode::ArrowLeft));
+        assert_eq!(decode(b"OP"), DecodedKey::RawKey(KeyCode::F1));
+        assert_eq!(decode(b"OS"), DecodedKey::RawKey(KeyCode::F4));
+        assert_eq!(decode(b"[15~"), DecodedKey::RawKey(KeyCode::F5));
+        assert_eq!(decode(b"[6~"), DecodedKey::RawKey(KeyCode::PageDown));
+    }
+
+    #[test]
+    fn lone_or_unknown_escapes_stay_escape() {
+        assert_eq!(decode(b""), DecodedKey::Unicode('\u{1b}'));
+        assert_eq!(decode(b"[99~"), DecodedKey::Unicode('\u{1b}'));
+        assert_eq!(decode(b"OX"), DecodedKey::Unicode('\u{1b}'));
+    }
+}
@@ -0,0 +1,100 @@
+// A command typed into a shell window. Words are separated by spaces; file
+// names and process IDs are passed on as typed, for the kernel to look up.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Command<'a> {
+    Ls,
+    Cat(&'a str),
+    Rm(&'a str),
+    Mv(&'a str, &'a str),
+    Cp(&'a str, &'a str),
+    Run(&'a str, Option<usize>),
+    Kill(usize),
+    Ps,
+    Df,
+    Help,
+    Exit,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ParseError<'a> {
+    Unknown(&'a str),
+    // How the command is used, as listed by help.
+    Usage(&'static str),
+}
+
+// Each command's usage and what it does, as listed by help. Lines fit in a
+// window.
+pub const HELP: &[(&str, &str)] = &[
+    ("ls", "list files"),
+    ("cat FILE", "show a file"),
+    ("rm FILE", "delete a file"),
+    ("mv FILE NAME", "rename a file"),
+    ("cp FILE NAME", "copy a file"),
+    ("run FILE [N]", "run, in FN if given"),
+    ("kill PID", "stop a program"),
+    ("ps", "list programs"),
+    ("df", "show disk usage"),
+    ("help", "show this list"),
+    ("exit", "close the shell"),
+];
+
+// The command on `line`, or None if the line is blank.
+pub fn parse(line: &str) -> Result<Option<Command<'_>>, ParseError<'_>> {
+    let mut words = line.split_whitespace();
+    let name = match words.next() {
+        Some(name) => name,
+        None => return Ok(None),
+    };
+    let args = [words.next(), words.next(), words.next()];
+    let command = match (name, args) {
+        ("ls", [None, None, None]) => Command::Ls,
+        ("cat", [Some(file), None, None]) => Command::Cat(file),
+        ("rm", [Some(file), None, None]) => Command::Rm(file),
+        ("mv", [Some(from), Some(to), None]) => Command::Mv(from, to),
+        ("cp", [Some(from), Some(to), None]) => Command::Cp(from, to),
+        ("run", [Some(file), None, None]) => Command::Run(file, None),
+        ("run", [Some(file), Some(window), None]) => Command::Run(file, Some(number(name, window)?)),
+        ("kill", [Some(pid), None, None]) => Command::Kill(number(name, pid)?),
+        ("ps", [None, None, None]) => Command::Ps,
+        ("df", [None, None, None]) => Command::Df,
+        ("help", [None, None, None]) => Command::Help,
+        ("exit", [None, None, None]) => Command::Exit,
+        (name, _) => return Err(usage(name)),
+    };
+    Ok(Some(command))
+}
+
+fn number<'a>(name: &'a str, word: &str) -> Result<usize, ParseError<'a>> {
+    word.parse().map_err(|_| usage(name))
+}
+
+fn usage(name: &str) -> ParseError<'_> {
+    match HELP.iter().find(|(usage, _)| usage.split(' ').next() == Some(name)) {
+        Some((usage, _)) => ParseError::Usage(usage),
+        None => ParseError::Unknown(name),
+    }
+}
+
+#[cfg(all(test, not(target_os = "none")))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn commands_take_their_arguments_in_order() {
+        assert_eq!(parse("ls"), Ok(Some(Command::Ls)));
+        assert_eq!(parse("  mv  old new "), Ok(Some(Command::Mv("old", "new"))));
+        assert_eq!(parse("run pi"), Ok(Some(Command::Run("pi", None))));
+        assert_eq!(parse("run pi 3"), Ok(Some(Command::Run("pi", Some(3)))));
+        assert_eq!(parse("kill 12"), Ok(Some(Command::Kill(12))));
+        assert_eq!(parse(""), Ok(None));
+    }
+
+    #[test]
+    fn wrong_arguments_show_the_usage() {
+        assert_eq!(parse("cat"), Err(ParseError::Usage("cat FILE")));
+        assert_eq!(parse("ls -l"), Err(ParseError::Usage("ls")));
+        assert_eq!(parse("kill me"), Err(ParseError::Usage("kill PID")));
+        assert_eq!(parse("run pi F2"), Err(ParseError::Usage("run FILE [N]")));
+        assert_eq!(parse("format c"), Err(ParseError::Unknown("format")));
+    }
+}
@@ -0,0 +1,65 @@
+use core::sync::atomic::{AtomicBool, Ordering};
+use x86_64::instructions::port::Port;
+
+// Support for the integration tests in tests/, which run in QEMU with an
+// isa-debug-exit device at port 0xf4 (see test-args in Cargo.toml). QEMU exits
+// with status (code << 1) | 1, so Success comes out as 33.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u32)]
+pub enum QemuExitCode {
+    Success = 0x10,
+    Failed = 0x11,
+}
+
+// Set once the tests start, so that the panic handler fails them rather than
+// showing the fault screen.
+static RUNNING: AtomicBool = AtomicBool::new(false);
+
+pub fn running() -> bool {
+    RUNNING.load(Ordering::SeqCst)
+}
+
+pub fn exit_qemu(code: QemuExitCode) -> ! {
+    unsafe { Port::new(0xf4).write(code as u32) };
+    loop {
+        x86_64::instructions::hlt();
+    }
+}
+
+// A test reports failure by returning Err, which lets the rest of the tests
+// run; a panic fails the whole run. Use `ensure!` to check conditions.
+pub trait Testable {
+    // ensure! has already printed why a test failed, so Err carries nothing.
+    #[allow(clippy::result_unit_err)]
+    fn run(&self) -> Result<(), ()>;
+}
+
+impl<T: Fn() -> Result<(), ()>> Testable for T {
+    fn run(&self) -> Result<(), ()> {
+        serial_print!("{}...\t", core::any::type_name::<T>());
+        let result = self();
+        serial_println!("{}", if result.is_ok() {"[ok]"} else {"[failed]"});
+        result
+    }
+}
+
+pub fn test_runner(tests: &[&dyn Testable]) {
+    RUNNING.store(true, Ordering::SeqCst);
+    serial_println!("Running {} tests", tests.len());
+    let failed = tests.iter().filter(|test| test.run().is_err()).count();
+    exit_qemu(if failed == 0 {QemuExitCode::Success} else {QemuExitCode::Failed});
+}
+
+// Fails the current test with a message on the serial port unless `cond`
+// holds.
+#[macro_export]
+macro_rules! ensure {
+    ($cond:expr, $($arg:tt)*) => {
+        if !$cond {
+            $crate::serial_println!();
+            $crate::serial_println!($($arg)*);
+            return Err(());
+        }
+    };
+}
@@ -0,0 +1,179 @@
+// These run in QEMU with `cargo ktest`; on the host there is nothing here.
+#![cfg(target_os = "none")]
+#![no_std]
+#![no_main]
+#![feature(custom_test_frameworks)]
+#![test_runner(swim_template::testing::test_runner)]
+#![reexport_test_harness_main = "test_main"]
+
+use core::mem::MaybeUninit;
+use core::ptr::addr_of_mut;
+use pc_keyboard::{DecodedKey, KeyCode};
+use swim_template::display::MemoryDisplay;
+use swim_template::{ensure, Kernel};
+
+#[no_mangle]
+pub extern "C" fn _start() -> ! {
+    test_main();
+    loop {}
+}
+
+// Upper bound on instructions a test runs, so that a program that never
+// finishes fails the test instead of hanging it.
+const MAX_STEPS: usize = 10_000;
+
+type TestKernel = Kernel<MemoryDisplay>;
+
+static mut SLOT: MaybeUninit<TestKernel> = MaybeUninit::uninit();
+
+// Tests run one after another, so each builds its kernel over the last
+// test's in the one slot.
+fn kernel() -> &'static mut TestKernel {
+    let kernel = Kernel::init_in(unsafe { &mut *addr_of_mut!(SLOT) }, MemoryDisplay::new());
+    kernel.draw();
+    kernel
+}
+
+fn press(kernel: &mut TestKernel, key: KeyCode) {
+    kernel.key(DecodedKey::RawKey(key));
+}
+
+fn type_text(kernel: &mut TestKernel, text: &str) {
+    for c in text.chars() {
+        kernel.key(DecodedKey::Unicode(c));
+    }
+}
+
+// Files are listed in name order (add_one, average, countdown, hello, nums,
+// pi), so file `index` is that many presses of the right arrow away.
+fn run_file(kernel: &mut TestKernel, window: KeyCode, index: usize) {
+    press(kernel, window);
+    for _ in 0..index {
+        press(kernel, KeyCode::ArrowRight);
+    }
+    type_text(kernel, "r");
+}
+
+// Runs instructions until `text` shows up in `window`, returning where.
+fn run_until(kernel: &mut TestKernel, window: usize, text: &str) -> Option<(usize, usize)> {
+    for _ in 0..MAX_STEPS {
+        if let Some(spot) = kernel.find_in_window(window, text) {
+            return Some(spot);
+        }
+        kernel.run_one_instruction();
+    }
+    kernel.find_in_window(window, text)
+}
+
+#[test_case]
+fn hello_prints_then_finishes() -> Result<(), ()> {
+    let mut kernel = kernel();
+    run_file(&mut kernel, KeyCode::F1, 3);
+    let done = run_until(&mut kernel, 1, "[DONE]");
+    ensure!(done.is_some(), "hello never finished");
+    let hello = kernel.find_in_window(1, "Hello, world!");
+    ensure!(hello.is_some(), "hello printed nothing");
+    ensure!(hello.unwrap().0 < done.unwrap().0, "[DONE] came before the output");
+    Ok(())
+}
+
+#[test_case]
+fn countdown_reads_its_input() -> Result<(), ()> {
+    let mut kernel = kernel();
+    run_file(&mut kernel, KeyCode::F1, 2);
+    ensure!(run_until(&mut kernel, 1, "count").is_some(), "countdown never asked for input");
+    for _ in 0..MAX_STEPS {
+        kernel.run_one_instruction();
+    }
+    type_text(&mut kernel, "3\n");
+    ensure!(run_until(&mut kernel, 1, "[DONE]").is_some(), "countdown never finished");
+    ensure!(kernel.find_in_window(1, "done").is_some(), "countdown did not print done");
+    Ok(())
+}
+
+#[test_case]
+fn windows_run_side_by_side() -> Result<(), ()> {
+    let mut kernel = kernel();
+    run_file(&mut kernel, KeyCode::F1, 3);
+    run_file(&mut kernel, KeyCode::F2, 4);
+    ensure!(run_until(&mut kernel, 1, "[DONE]").is_some(), "hello never finished in F1");
+    ensure!(run_until(&mut kernel, 2, "[DONE]").is_some(), "nums never finished in F2");
+    ensure!(kernel.find_in_window(2, "257").is_some(), "nums did not print 257 in F2");
+    ensure!(kernel.find_in_window(1, "257").is_none(), "nums output leaked into F1");
+    Ok(())
+}
+
+#[test_case]
+fn an_error_stops_only_its_own_window() -> Result<(), ()> {
+    let mut kernel = kernel();
+    press(&mut kernel, KeyCode::F5);
+    type_text(&mut kernel, "bad\n");
+    press(&mut kernel, KeyCode::F1);
+    // New files are listed after the six programs.
+    for _ in 0..6 {
+        press(&mut kernel, KeyCode::ArrowRight);
+    }
+    type_text(&mut kernel, "eprint(missing)");
+    press(&mut kernel, KeyCode::F6);
+    run_file(&mut kernel, KeyCode::F1, 6);
+    run_file(&mut kernel, KeyCode::F2, 3);
+    ensure!(run_until(&mut kernel, 1, "[ERROR]").is_some(), "the bad program did not report an error");
+    ensure!(run_until(&mut kernel, 2, "[DONE]").is_some(), "hello stopped when F1 failed");
+    Ok(())
+}
+
+#[test_case]
+fn backspace_edits_program_input() -> Result<(), ()> {
+    let mut kernel = kernel();
+    run_file(&mut kernel, KeyCode::F1, 1);
+    for line in ["1x\u{08}\n", "3\n", "quit\n"] {
+        for _ in 0..MAX_STEPS {
+            kernel.run_one_instruction();
+        }
+        type_text(&mut kernel, line);
+    }
+    ensure!(run_until(&mut kernel, 1, "[DONE]").is_some(), "average never finished");
+    ensure!(kernel.find_in_window(1, "2").is_some(), "average of 1 and 3 was not 2");
+    Ok(())
+}
+
+#[test_case]
+fn answers_can_be_typed_ahead() -> Result<(), ()> {
+    let mut kernel = kernel();
+    run_file(&mut kernel, KeyCode::F1, 1);
+    type_text(&mut kernel, "1\n3\nquit\n");
+    ensure!(run_until(&mut kernel, 1, "[DONE]").is_some(), "average never finished");
+    ensure!(kernel.find_in_window(1, "2").is_some(), "average of 1 and 3 was not 2");
+    Ok(())
+}
+
+#[test_case]
+fn background_jobs_show_their_output_once_attached() -> Result<(), ()> {
+    let mut kernel = kernel();
+    run_file(&mut kernel, KeyCode::F1, 4);
+    press(&mut kernel, KeyCode::F2);
+    for _ in 0..4 {
+        press(&mut kernel, KeyCode::ArrowRight);
+    }
+    type_text(&mut kernel, "b");
+    for _ in 0..MAX_STEPS {
+        kernel.run_one_instruction();
+    }
+    ensure!(kernel.find_in_window(2, "257").is_none(), "a background job printed into F2");
+    type_text(&mut kernel, "a2\n");
+    ensure!(kernel.find_in_window(2, "257").is_some(), "the attached job's output was not shown");
+    ensure!(kernel.find_in_window(2, "[DONE]").is_some(), "the attached job did not finish");
+    Ok(())
+}
+
+#[test_case]
+fn the_shell_runs_programs_in_other_windows() -> Result<(), ()> {
+    let mut kernel = kernel();
+    type_text(&mut kernel, "c");
+    type_text(&mut kernel, "run nums 2\n");
+    ensure!(run_until(&mut kernel, 2, "[DONE]").is_some(), "nums never finished in F2");
+    ensure!(kernel.find_in_window(2, "257").is_some(), "nums did not print 257 in F2");
+    type_text(&mut kernel, "ps\n");
+    ensure!(kernel.find_in_window(1, "done     nums").is_some(), "ps did not list nums as done");
+    Ok(())
+}
@@ -0,0 +1,186 @@
+// Packs a directory of program files into the disk image the kernel mounts at
+// boot, using the layout in src/image.rs. It only needs the standard library,
+// so build it for the host with rustc:
+//
+//     rustc -O tools/mkdisk.rs -o target/mkdisk
+//     target/mkdisk [--overwrite] programs disk.img
+//
+// Run it again after changing programs/. An existing image keeps the files
+// the kernel saved in it, and programs it lacks are added. Where the image
+// has a different copy of a program, the image's copy is kept unless
+// --overwrite is given; either way mkdisk says which copy it kept.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+#[allow(dead_code)]
+#[path = "../src/image.rs"]
+mod image;
+
+use image::{BlockDevice, ImageLayout};
+
+// These must match the constants in src/lib.rs.
+const BLOCK_SIZE: usize = 256;
+const MAX_FILE_BLOCKS: usize = 8;
+const MAX_FILE_BYTES: usize = MAX_FILE_BLOCKS * BLOCK_SIZE;
+const MAX_FILES_STORED: usize = 30;
+const MAX_FILENAME_BYTES: usize = 10;
+
+type DiskLayout = ImageLayout<BLOCK_SIZE, MAX_FILE_BLOCKS, MAX_FILENAME_BYTES>;
+
+// Size of the images written, comfortably more than MAX_FILES_STORED files
+// need.
+pub const IMAGE_BYTES: usize = 128 * 1024;
+
+struct MemoryDisk {
+    bytes: Vec<u8>,
+}
+
+impl BlockDevice<BLOCK_SIZE> for MemoryDisk {
+    type Error = io::Error;
+
+    fn read_block(&mut self, block: usize, buffer: &mut [u8; BLOCK_SIZE]) -> io::Result<()> {
+        buffer.copy_from_slice(&self.bytes[block * BLOCK_SIZE..(block + 1) * BLOCK_SIZE]);
+        Ok(())
+    }
+
+    fn write_block(&mut self, block: usize, buffer: &[u8; BLOCK_SIZE]) -> io::Result<()> {
+        self.bytes[block * BLOCK_SIZE..(block + 1) * BLOCK_SIZE].copy_from_slice(buffer);
+        Ok(())
+    }
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
+
+// Program files in `dir` as (name, contents) pairs, sorted by name. Programs
+// that the kernel could not hold are an error rather than being truncated.
+pub fn read_programs(dir: &Path) -> io::Result<Vec<(String, Vec<u8>)>> {
+    let mut programs = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if !path.is_file() {
+            continue;
+        }
+        let name = path.file_name().and_then(|name| name.to_str())
+            .ok_or_else(|| invalid(format!("{}: file name is not UTF-8", path.display())))?
+            .to_string();
+        if name.len() > MAX_FILENAME_BYTES {
+            return Err(invalid(format!("{}: names are limited to {} bytes", name, MAX_FILENAME_BYTES)));
+        }
+        let contents = fs::read(&path)?;
+        if contents.len() > MAX_FILE_BYTES {
+            return Err(invalid(format!("{}: files are limited to {} bytes", name, MAX_FILE_BYTES)));
+        }
+        programs.push((name, contents));
+    }
+    if programs.len() > MAX_FILES_STORED {
+        return Err(invalid(format!("{}: at most {} files fit on a disk", dir.display(), MAX_FILES_STORED)));
+    }
+    programs.sort();
+    Ok(programs)
+}
+
+// The files on `disk`, in the order the kernel lists them. An unformatted
+// disk has none.
+fn read_files(disk: &mut MemoryDisk) -> io::Result<Vec<(String, Vec<u8>)>> {
+    let count = DiskLayout::read_count(disk)?.unwrap_or(0).min(MAX_FILES_STORED);
+    let mut files = Vec::new();
+    for i in 0..count {
+        let mut name = [0; MAX_FILENAME_BYTES];
+        let mut contents = vec![0; MAX_FILE_BYTES];
+        let len = DiskLayout::read_file(disk, i, &mut name, &mut contents)?;
+        contents.truncate(len);
+        let len = name.iter().position(|c| *c == 0).unwrap_or(MAX_FILENAME_BYTES);
+        let name = String::from_utf8(name[0..len].to_vec())
+            .map_err(|_| invalid(format!("file {} on the image has a name that is not UTF-8", i)))?;
+        files.push((name, contents));
+    }
+    Ok(files)
+}
+
+// What update_image did with a program that the image did not already have
+// an identical copy of.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Added,
+    // The image's copy was different, and was replaced.
+    Replaced,
+    // The image's copy was different, and was kept.
+    Kept,
+}
+
+// Brings the image at `image` up to date with the programs in `dir`, creating
+// it if there is none, and returns what happened to each program that was not
+// already on it as it is. Files already on the image stay in place so the
+// kernel finds them where it left them; new programs go after them.
+pub fn update_image(dir: &Path, image: &Path, overwrite: bool) -> io::Result<Vec<(String, Outcome)>> {
+    let programs = read_programs(dir)?;
+    let (mut disk, exists) = match fs::read(image) {
+        Ok(bytes) => (MemoryDisk {bytes}, true),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => (MemoryDisk {bytes: Vec::new()}, false),
+        Err(e) => return Err(e),
+    };
+    if disk.bytes.len() < IMAGE_BYTES {
+        disk.bytes.resize(IMAGE_BYTES, 0);
+    }
+    let mut files = read_files(&mut disk)?;
+    let mut outcomes = Vec::new();
+    for (name, contents) in programs {
+        let outcome = match files.iter_mut().find(|(file, _)| *file == name) {
+            Some(file) if file.1 == contents => continue,
+            Some(file) if overwrite => {
+                file.1 = contents;
+                Outcome::Replaced
+            }
+            Some(_) => Outcome::Kept,
+            None => {
+                files.push((name.clone(), contents));
+                Outcome::Added
+            }
+        };
+        outcomes.push((name, outcome));
+    }
+    if files.len() > MAX_FILES_STORED {
+        return Err(invalid(format!("{}: at most {} files fit on a disk", image.display(), MAX_FILES_STORED)));
+    }
+    if !exists || outcomes.iter().any(|(_, outcome)| *outcome != Outcome::Kept) {
+        for (i, (name, contents)) in files.iter().enumerate() {
+            DiskLayout::write_file(&mut disk, i, name.as_bytes(), contents)?;
+        }
+        DiskLayout::write_count(&mut disk, files.len())?;
+        fs::write(image, &disk.bytes)?;
+    }
+    Ok(outcomes)
+}
+
+fn main() {
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    let overwrite = args.first().is_some_and(|arg| arg == "--overwrite");
+    if overwrite {
+        args.remove(0);
+    }
+    if args.len() != 2 {
+        eprintln!("usage: mkdisk [--overwrite] <program directory> <image file>");
+        std::process::exit(1);
+    }
+    let (dir, image) = (&args[0], &args[1]);
+    match update_image(Path::new(dir), Path::new(image), overwrite) {
+        Ok(outcomes) if outcomes.is_empty() => println!("{} is up to date", image),
+        Ok(outcomes) => {
+            for (name, outcome) in outcomes {
+                match outcome {
+                    Outcome::Added => println!("added {}", name),
+                    Outcome::Replaced => println!("replaced {}'s copy of {} with {}/{}", image, name, dir, name),
+                    Outcome::Kept => println!("kept {}'s copy of {}, which differs from {}/{} (--overwrite replaces it)", image, name, dir, name),
+                }
+            }
+        }
+        Err(e) => {
+            eprintln!("mkdisk: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
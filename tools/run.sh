@@ -0,0 +1,12 @@
+#!/bin/sh
+# The cargo runner for kernel builds (see .cargo/config.toml). QEMU will not
+# start without the disk.img named in run-args, which a fresh checkout does
+# not have, so one is made from programs/ first. Like run-args, this expects
+# cargo to be run from the repository root.
+set -e
+if [ ! -e disk.img ]; then
+    mkdir -p target
+    rustc -O tools/mkdisk.rs -o target/mkdisk
+    target/mkdisk programs disk.img
+fi
+exec bootimage runner "$@"